@@ -0,0 +1,71 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Source of the time recorded for each [`call`](crate::call) call.
+///
+/// Set by [`CallRecorder::with_clock`](crate::CallRecorder::with_clock).
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// [`Clock`] that uses [`Instant::now`].
+///
+/// This is the default clock of [`CallRecorder`](crate::CallRecorder).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// [`Clock`] that advances only when [`ManualClock::advance`] is called.
+///
+/// Clones share the same time, so a clone can be kept to advance the clock after passing it to [`CallRecorder::with_clock`](crate::CallRecorder::with_clock).
+///
+/// # Examples
+///
+#[cfg_attr(feature = "record", doc = "```")]
+#[cfg_attr(not(feature = "record"), doc = "```ignore")]
+/// use assert_call::{call, clock::ManualClock, Call, CallRecorder};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let mut c = CallRecorder::new().with_clock(clock.clone());
+/// call!("try");
+/// clock.advance(Duration::from_secs(1));
+/// call!("retry");
+/// c.verify([
+///     Call::id("try"),
+///     Call::id("retry").after_delay(Duration::from_secs(1)),
+/// ]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManualClock(Arc<Mutex<Instant>>);
+
+impl ManualClock {
+    /// Create a clock that starts at the current time.
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// Advance the clock by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
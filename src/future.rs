@@ -0,0 +1,98 @@
+use std::fmt::Display;
+#[cfg(feature = "record")]
+use std::{
+    panic::Location,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "record")]
+use crate::records::Records;
+
+/// Extension trait to record the lifecycle of a [`Future`].
+pub trait RecordCallsExt: Future + Sized {
+    /// Wrap the future so that its lifecycle is recorded as [`call`](crate::call) calls.
+    ///
+    /// The following calls are recorded.
+    ///
+    /// - `{id}:poll` : The future is polled for the first time.
+    /// - `{id}:ready` : The future returned [`Poll::Ready`].
+    /// - `{id}:drop` : The future is dropped before returning [`Poll::Ready`].
+    ///
+    /// If the `record` feature is disabled, returns the future itself and records nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assert_call::{future::RecordCallsExt, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let f = async {}.record_calls("download");
+    /// drop(f);
+    /// c.verify("download:drop");
+    /// ```
+    #[cfg(feature = "record")]
+    #[track_caller]
+    fn record_calls(self, id: impl Display) -> RecordCalls<Self> {
+        let location = Location::caller();
+        RecordCalls {
+            id: id.to_string(),
+            file: location.file(),
+            line: location.line(),
+            future: Box::pin(self),
+            is_polled: false,
+            is_ready: false,
+        }
+    }
+
+    #[cfg(not(feature = "record"))]
+    fn record_calls(self, id: impl Display) -> RecordCalls<Self> {
+        let _ = id;
+        self
+    }
+}
+impl<F: Future> RecordCallsExt for F {}
+
+/// Future created by [`RecordCallsExt::record_calls`].
+#[cfg(feature = "record")]
+pub struct RecordCalls<F> {
+    id: String,
+    file: &'static str,
+    line: u32,
+    future: Pin<Box<F>>,
+    is_polled: bool,
+    is_ready: bool,
+}
+
+/// Future created by [`RecordCallsExt::record_calls`].
+///
+/// The future itself because the `record` feature is disabled.
+#[cfg(not(feature = "record"))]
+pub type RecordCalls<F> = F;
+
+#[cfg(feature = "record")]
+impl<F: Future> Future for RecordCalls<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if !self.is_polled {
+            self.is_polled = true;
+            Records::push(format!("{}:poll", self.id), self.file, self.line);
+        }
+        let value = self.future.as_mut().poll(cx);
+        if value.is_ready() && !self.is_ready {
+            self.is_ready = true;
+            Records::push(format!("{}:ready", self.id), self.file, self.line);
+        }
+        value
+    }
+}
+#[cfg(feature = "record")]
+impl<F> Drop for RecordCalls<F> {
+    fn drop(&mut self) {
+        if !self.is_ready {
+            // Do not panic here to avoid a double panic when unwinding.
+            Records::try_push(format!("{}:drop", self.id), self.file, self.line);
+        }
+    }
+}
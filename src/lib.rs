@@ -29,6 +29,7 @@
 //!
 //! mismatch call
 //! src\lib.rs:10
+//! thread : main
 //! actual : 2
 //! expect : 3
 //! ```
@@ -38,26 +39,48 @@
 //! If backtrace capture is enabled at [`Backtrace::capture`],
 //! [`CallRecorder::verify`] outputs detailed information including the backtrace for each [`call!`] call.
 //!
+//! # Features
+//!
+//! - `record` : Enabled by default. Enables recording by [`call!`], [`call_if_recording!`], [`call_result!`], [`call_span!`]
+//!   and [`RecordCallsExt::record_calls`](future::RecordCallsExt::record_calls).
+//!   If disabled, these macros expand to code that does nothing and does not evaluate the arguments for the call ID and fields,
+//!   and `record_calls` returns the future itself, so they can be left in production code at no cost.
+//!   To record calls only in tests, disable the default features in `[dependencies]` and enable `record` in `[dev-dependencies]`.
+//! - `regex` : Enables [`Call::regex`].
+//!
 use std::{
     backtrace::{Backtrace, BacktraceStatus},
     collections::VecDeque,
     error::Error,
-    fmt::Display,
+    fmt::{self, Debug, Display},
+    hash::{Hash, Hasher},
+    mem::{replace, take},
+    ops::{Bound, RangeBounds},
+    slice,
+    sync::Arc,
     thread::{self, ThreadId},
+    time::{Duration, Instant},
 };
 
-use records::{Global, Local, Records, Thread};
+use clock::Clock;
+use records::{Global, Local, RecordKind, RecordView, Records, Scope, Task, Thread};
 use yansi::Condition;
 
+pub mod clock;
+pub mod future;
 pub mod records;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "record"))]
 mod tests;
 
 /// Record the call.
 ///
 /// The argument is the call ID with the same format as [`std::format`].
 ///
+/// The call ID can be followed by `;` and fields in the form of `key = value`.
+/// The values of the fields are recorded by their [`Debug`](std::fmt::Debug) representation
+/// and can be verified by [`Call::with`].
+///
 /// # Panics
 ///
 /// Panics if [`CallRecorder`] is not initialized.
@@ -75,17 +98,212 @@ mod tests;
 ///
 /// call!("1");
 /// call!("{}-{}", 1, 2);
+/// call!("write"; fd = 3, len = 1024);
+/// call!("write-{}", 1; fd = 3);
 /// ```
+#[cfg(feature = "record")]
 #[macro_export]
 macro_rules! call {
+    ($($id:expr),+ ; $($key:ident = $value:expr),* $(,)?) => {
+        $crate::records::Records::push_with_fields(
+            ::std::format!($($id),+),
+            ::std::vec![$((::std::stringify!($key), ::std::format!("{:?}", $value))),*],
+            ::std::file!(),
+            ::std::line!(),
+        );
+    };
     ($($id:tt)*) => {
         $crate::records::Records::push(::std::format!($($id)*), ::std::file!(), ::std::line!());
     };
 }
 
+/// Record the call.
+///
+/// Does nothing because the `record` feature is disabled.
+#[cfg(not(feature = "record"))]
+#[macro_export]
+macro_rules! call {
+    ($($id:expr),+ ; $($key:ident = $value:expr),* $(,)?) => {
+        if false {
+            let _ = (::std::format_args!($($id),+), $(&$value),*);
+        }
+    };
+    ($($id:tt)*) => {
+        if false {
+            let _ = ::std::format_args!($($id)*);
+        }
+    };
+}
+
+/// Record the call if [`CallRecorder`] is initialized, and do nothing otherwise.
+///
+/// The arguments are the same as [`call`].
+/// The arguments are not evaluated if `CallRecorder` is not initialized.
+///
+/// Unlike [`call`], this macro can be left in code that also runs without `CallRecorder`,
+/// such as tests that do not verify calls.
+/// Note that calls from such code are recorded by a `CallRecorder` created by [`CallRecorder::new`] in another test running at the same time.
+///
+/// # Examples
+///
+/// ```
+/// use assert_call::{call_if_recording, CallRecorder};
+///
+/// call_if_recording!("ignored");
+///
+/// let mut c = CallRecorder::new_local();
+/// call_if_recording!("1");
+/// call_if_recording!("write"; fd = 3);
+/// c.verify(["1", "write"]);
+/// ```
+#[cfg(feature = "record")]
+#[macro_export]
+macro_rules! call_if_recording {
+    ($($id:expr),+ ; $($key:ident = $value:expr),* $(,)?) => {
+        if $crate::records::Records::is_recording() {
+            $crate::records::Records::try_push_with_fields(
+                ::std::format!($($id),+),
+                ::std::vec![$((::std::stringify!($key), ::std::format!("{:?}", $value))),*],
+                ::std::file!(),
+                ::std::line!(),
+            );
+        }
+    };
+    ($($id:tt)*) => {
+        if $crate::records::Records::is_recording() {
+            $crate::records::Records::try_push(::std::format!($($id)*), ::std::file!(), ::std::line!());
+        }
+    };
+}
+
+/// Record the call if [`CallRecorder`] is initialized, and do nothing otherwise.
+///
+/// Does nothing because the `record` feature is disabled.
+#[cfg(not(feature = "record"))]
+#[macro_export]
+macro_rules! call_if_recording {
+    ($($id:tt)*) => {
+        $crate::call!($($id)*)
+    };
+}
+
+/// Record the call and the result of the expression.
+///
+/// The first argument is the call ID, and the second argument is an expression of type [`Result`].
+/// The call is recorded before the expression is evaluated,
+/// and the [`Debug`](std::fmt::Debug) representation of the `Ok` or `Err` value is added to the record after evaluation.
+///
+/// Returns the value of the expression.
+///
+/// The result can be verified by [`Call::returns_ok`] and [`Call::returns_err`].
+///
+/// # Panics
+///
+/// Panics if [`CallRecorder`] is not initialized.
+///
+/// # Examples
+///
+/// ```
+/// use assert_call::{call_result, Call, CallRecorder};
+///
+/// fn fetch(s: &str) -> Result<u32, std::num::ParseIntError> {
+///     call_result!("fetch", s.parse())
+/// }
+///
+/// let mut c = CallRecorder::new();
+/// let _ = fetch("1");
+/// let _ = fetch("a");
+/// c.verify([
+///     Call::id("fetch").returns_ok(),
+///     Call::id("fetch").returns_err(),
+/// ]);
+/// ```
+#[cfg(feature = "record")]
+#[macro_export]
+macro_rules! call_result {
+    ($id:expr, $e:expr $(,)?) => {{
+        let key = $crate::records::Records::push_result_entry(
+            ::std::string::ToString::to_string(&$id),
+            ::std::file!(),
+            ::std::line!(),
+        );
+        let r = $e;
+        $crate::records::Records::set_result(key, &r);
+        r
+    }};
+}
+
+/// Record the call and the result of the expression.
+///
+/// Only evaluates the expression because the `record` feature is disabled.
+#[cfg(not(feature = "record"))]
+#[macro_export]
+macro_rules! call_result {
+    ($id:expr, $e:expr $(,)?) => {{
+        if false {
+            let _ = &$id;
+        }
+        $e
+    }};
+}
+
+/// Record the enter of the span, and record the exit of the span when the returned guard is dropped.
+///
+/// The argument is the span ID with the same format as [`std::format`].
+///
+/// Calls recorded while the guard is alive in the same thread are nested in the span,
+/// and can be verified by [`Call::within`].
+/// The exit is recorded even if the guard is dropped by unwinding.
+///
+/// # Panics
+///
+/// Panics if [`CallRecorder`] is not initialized.
+///
+/// # Examples
+///
+/// ```
+/// use assert_call::{call, call_span, Call, CallRecorder};
+///
+/// fn a() {
+///     let _s = call_span!("a");
+///     call!("1");
+///     b();
+/// }
+/// fn b() {
+///     let _s = call_span!("b");
+///     call!("2");
+/// }
+///
+/// let mut c = CallRecorder::new();
+/// a();
+/// c.verify(Call::within("a", [Call::id("1"), Call::within("b", "2")]));
+/// ```
+#[cfg(feature = "record")]
+#[macro_export]
+macro_rules! call_span {
+    ($($id:tt)*) => {
+        $crate::records::SpanGuard::enter(::std::format!($($id)*), ::std::file!(), ::std::line!())
+    };
+}
+
+/// Record the enter of the span, and record the exit of the span when the returned guard is dropped.
+///
+/// Does nothing because the `record` feature is disabled.
+#[cfg(not(feature = "record"))]
+#[macro_export]
+macro_rules! call_span {
+    ($($id:tt)*) => {{
+        if false {
+            let _ = ::std::format_args!($($id)*);
+        }
+        $crate::records::SpanGuard::noop()
+    }};
+}
+
 /// Records and verifies calls to [`call`].
 pub struct CallRecorder<T: Thread = Global> {
     thread: T,
+    on_drop: Option<Call>,
 }
 impl CallRecorder {
     /// Start recording [`call`] macro calls in all threads.
@@ -106,223 +324,1695 @@ impl CallRecorder<Local> {
         Self::new_raw()
     }
 }
-impl<T: Thread> CallRecorder<T> {
-    fn new_raw() -> Self {
-        Self { thread: T::init() }
+impl CallRecorder<Task> {
+    /// Start recording [`call`] macro calls in futures wrapped by [`CallRecorder::scope`].
+    ///
+    /// Unlike [`CallRecorder::new`], multiple instances can exist at the same time,
+    /// so async tests running concurrently on a shared runtime do not wait for each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// async fn run() {
+    ///     let mut c = CallRecorder::new_task();
+    ///     c.scope(async {
+    ///         call!("1");
+    ///         call!("2");
+    ///     })
+    ///     .await;
+    ///     c.verify(["1", "2"]);
+    /// }
+    /// ```
+    pub fn new_task() -> Self {
+        Self::new_raw()
+    }
+
+    /// Wrap `future` so that [`call`] calls made while it is polled are recorded by this `CallRecorder`.
+    ///
+    /// The returned future follows `future` across `.await` points even if it is polled on different threads.
+    /// Tasks spawned inside `future` are not recorded unless they are also wrapped by this method.
+    pub fn scope<F: Future>(&self, future: F) -> Scope<F> {
+        self.thread.scope(future)
+    }
+}
+impl<T: Thread> CallRecorder<T> {
+    fn new_raw() -> Self {
+        Self {
+            thread: T::init(),
+            on_drop: None,
+        }
+    }
+
+    /// Verify that [`call`] calls remaining when this `CallRecorder` is dropped match the expected pattern.
+    ///
+    /// The verification is skipped if the thread is already panicking.
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let c = CallRecorder::new().expect_on_drop(["1", "2"]);
+    /// call!("1");
+    /// ```
+    pub fn expect_on_drop(mut self, expect: impl ToCall) -> Self {
+        self.on_drop = Some(expect.to_call());
+        self
+    }
+
+    /// Verify that no [`call`] calls remain unverified when this `CallRecorder` is dropped.
+    ///
+    /// Equivalent to `expect_on_drop(Call::empty())`.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```should_panic")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let c = CallRecorder::new().deny_unverified();
+    /// call!("1");
+    /// ```
+    pub fn deny_unverified(self) -> Self {
+        self.expect_on_drop(Call::empty())
+    }
+
+    /// Use `clock` as the source of the time recorded for each [`call`] call.
+    ///
+    /// By default, [`Instant::now`] is used.
+    /// Using [`ManualClock`](clock::ManualClock) makes timing-aware patterns such as [`Call::after_delay`] deterministic.
+    pub fn with_clock(self, clock: impl Clock) -> Self {
+        self.thread.set_clock(Arc::new(clock));
+        self
+    }
+
+    /// Panic if [`call`] call does not match the expected pattern.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    #[track_caller]
+    pub fn verify(&mut self, expect: impl ToCall) {
+        self.verify_with_msg(expect, "mismatch call");
+    }
+
+    /// Panic with specified message if [`call`] call does not match the expected pattern.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    #[track_caller]
+    pub fn verify_with_msg(&mut self, expect: impl ToCall, msg: &str) {
+        self.result_with_msg(expect, msg).unwrap_or_panic();
+    }
+
+    /// Panic if the first [`call`] calls do not match the expected pattern.
+    ///
+    /// Only the calls matched by the pattern are cleared,
+    /// and the remaining calls are kept for subsequent verifications.
+    /// If the verification fails, all recorded calls are cleared.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// call!("2");
+    /// call!("3");
+    /// c.verify_prefix(["1", "2"]);
+    /// c.verify("3");
+    /// ```
+    #[track_caller]
+    pub fn verify_prefix(&mut self, expect: impl ToCall) {
+        let expect = expect.to_call();
+        self.thread
+            .with_actual(|actual| expect.verify_prefix(actual, "mismatch call"))
+            .unwrap_or_panic();
+    }
+
+    /// Panic if [`call`] calls do not contain the expected pattern as a subsequence.
+    ///
+    /// Calls that do not match the pattern are ignored. Equivalent to `verify(Call::ignore_others(expect))`.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// call!("debug");
+    /// call!("2");
+    /// c.verify_contains(["1", "2"]);
+    /// ```
+    #[track_caller]
+    pub fn verify_contains(&mut self, expect: impl ToCall) {
+        self.verify(Call::ignore_others(expect));
+    }
+
+    /// Panic if [`call`] calls contain a part that matches the specified pattern.
+    ///
+    /// If the verification succeeds, the recorded [`call`] calls are kept.
+    /// If the verification fails, the recorded [`call`] calls are cleared.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("db.begin");
+    /// call!("db.commit");
+    /// c.verify_not_called("db.rollback");
+    /// c.verify(["db.begin", "db.commit"]);
+    /// ```
+    #[track_caller]
+    pub fn verify_not_called(&mut self, pattern: impl ToCall) {
+        let pattern = pattern.to_call();
+        self.thread
+            .with_actual(|actual| pattern.verify_not_found(actual, "unexpected call"))
+            .unwrap_or_panic();
+    }
+
+    /// Panic if [`call`] calls on each thread do not match the pattern specified for the thread.
+    ///
+    /// The recorded calls are partitioned by thread, and each partition is verified independently.
+    /// Threads are specified by [`ThreadId`] or thread name.
+    /// Threads that recorded calls but are not specified are expected to have no calls.
+    ///
+    /// Panics if a thread name matches more than one thread that recorded calls.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    /// use std::thread;
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let (a, b) = thread::scope(|s| {
+    ///     let a = s.spawn(|| {
+    ///         call!("a1");
+    ///         call!("a2");
+    ///     });
+    ///     let b = s.spawn(|| {
+    ///         call!("b1");
+    ///         call!("b2");
+    ///     });
+    ///     (a.thread().id(), b.thread().id())
+    /// });
+    /// c.verify_per_thread([(a, ["a1", "a2"]), (b, ["b1", "b2"])]);
+    /// ```
+    #[track_caller]
+    pub fn verify_per_thread<K: Into<ThreadTag>, P: ToCall>(
+        &mut self,
+        expect: impl IntoIterator<Item = (K, P)>,
+    ) {
+        let mut expect: Vec<(ThreadTag, Call)> = expect
+            .into_iter()
+            .map(|(k, p)| (k.into(), p.to_call()))
+            .collect();
+        let actual = self.thread.take_actual();
+        let mut threads = Vec::new();
+        for a in &actual.0 {
+            if !threads.contains(&a.thread_id) {
+                threads.push(a.thread_id);
+            }
+        }
+        for (thread, _) in &expect {
+            if let ThreadTag::Name(name) = thread {
+                let count = threads
+                    .iter()
+                    .filter(|&&id| {
+                        actual
+                            .0
+                            .iter()
+                            .any(|a| a.thread_id == id && thread.is_match(a))
+                    })
+                    .count();
+                if count > 1 {
+                    panic!("thread name `{name}` matches {count} threads, use `ThreadId` instead");
+                }
+            }
+        }
+        for thread_id in threads {
+            let records: Vec<_> = actual
+                .0
+                .iter()
+                .filter(|a| a.thread_id == thread_id)
+                .cloned()
+                .collect();
+            let expect = match expect.iter().position(|e| e.0.is_match(&records[0])) {
+                Some(index) => expect.remove(index).1,
+                None => Call::empty(),
+            };
+            let msg = format!("mismatch call on thread {}", records[0].fmt_thread());
+            expect.verify(Records(records), &msg).unwrap_or_panic();
+        }
+        for (thread, expect) in expect {
+            let msg = format!("mismatch call on thread {thread}");
+            expect.verify(Records::empty(), &msg).unwrap_or_panic();
+        }
+    }
+
+    /// Wait until [`call`] calls match the expected pattern, and panic if they do not match within `timeout`.
+    ///
+    /// Each time a call is recorded, the calls are verified against the pattern.
+    /// This method returns as soon as the calls match,
+    /// and panics as soon as the calls can no longer match or `timeout` elapses.
+    ///
+    /// For a `CallRecorder` created by [`CallRecorder::new_local`], this method does not wait
+    /// because no other thread can record calls.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    /// use std::{thread, time::Duration};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// thread::spawn(|| {
+    ///     call!("1");
+    ///     call!("2");
+    /// });
+    /// c.verify_eventually(["1", "2"], Duration::from_secs(10));
+    /// ```
+    #[track_caller]
+    pub fn verify_eventually(&mut self, expect: impl ToCall, timeout: Duration) {
+        let expect = expect.to_call();
+        let msg = "mismatch call";
+        let start = Instant::now();
+        let mut checks = 0;
+        let r = self.thread.wait_actual(timeout, |actual| {
+            checks += 1;
+            let mut ignored = Vec::new();
+            match expect.clone().verify_nexts(&actual.0, &mut ignored) {
+                Ok(_) => {
+                    take(actual);
+                    Some(Ok(()))
+                }
+                Err(e) if e.0.mismatch_index < actual.len() => {
+                    Some(Err(e.finish(take(actual), ignored, msg)))
+                }
+                Err(_) => None,
+            }
+        });
+        // The calls are checked again each time the wait ends.
+        let waited = (checks > 1).then(|| start.elapsed());
+        let r = r.unwrap_or_else(|| self.result_with_msg(expect, msg));
+        r.map_err(|mut e| {
+            e.0.waited = waited;
+            e
+        })
+        .unwrap_or_panic();
+    }
+
+    /// Returns the number of recorded [`call`] calls with the specified ID.
+    ///
+    /// Calling this method does not clear the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("poll");
+    /// call!("poll");
+    /// call!("done");
+    /// assert_eq!(c.count("poll"), 2);
+    /// ```
+    pub fn count(&self, id: impl Display) -> usize {
+        let id = id.to_string();
+        self.thread.with_actual(|actual| {
+            actual
+                .0
+                .iter()
+                .filter(|a| a.is_call() && a.id == id)
+                .count()
+        })
+    }
+
+    /// Returns the number of non-overlapping parts of the recorded [`call`] calls that match the specified pattern.
+    ///
+    /// Calling this method does not clear the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("req-1");
+    /// call!("res-1");
+    /// call!("req-2");
+    /// call!("res-2");
+    /// assert_eq!(c.count_matching(Call::glob("req-*")), 2);
+    /// assert_eq!(c.count_matching(["req-*", "res-*"].map(Call::glob)), 2);
+    /// ```
+    pub fn count_matching(&self, pattern: impl ToCall) -> usize {
+        let pattern = pattern.to_call();
+        self.thread.with_actual(|actual| pattern.count(&actual.0))
+    }
+
+    /// Wait until a part of the recorded [`call`] calls matches the specified pattern.
+    ///
+    /// Returns the index of the last call in the first part that matches the pattern,
+    /// or [`TimeoutError`] if no part matches within `timeout`.
+    ///
+    /// For a `CallRecorder` created by [`CallRecorder::new_local`], this method does not wait
+    /// because no other thread can record calls.
+    ///
+    /// Calling this method does not clear the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    /// use std::{thread, time::Duration};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let h = thread::spawn(|| {
+    ///     call!("start");
+    ///     call!("ready");
+    /// });
+    /// assert_eq!(c.wait_for("ready", Duration::from_secs(10)).unwrap(), 1);
+    /// h.join().unwrap();
+    /// c.verify(["start", "ready"]);
+    /// ```
+    pub fn wait_for(&self, pattern: impl ToCall, timeout: Duration) -> Result<usize, TimeoutError> {
+        let pattern = pattern.to_call();
+        self.thread
+            .wait_actual(timeout, |actual| {
+                let index = pattern.find(&actual.0)?;
+                let len = pattern.match_len(&actual.0[index..])?;
+                Some(index + len - 1)
+            })
+            .ok_or_else(|| TimeoutError {
+                expect: pattern.expects(),
+                timeout,
+            })
+    }
+
+    /// Returns `true` if no [`call`] calls are recorded.
+    ///
+    /// Calling this method does not clear the recorded [`call`] calls.
+    pub fn is_empty(&self) -> bool {
+        self.thread.with_actual(|actual| actual.0.is_empty())
+    }
+
+    /// Returns a copy of the recorded [`call`] calls.
+    ///
+    /// Calling this method does not clear the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// call!("2");
+    /// let ids: Vec<_> = c.snapshot().iter().map(|r| r.id().to_string()).collect();
+    /// assert_eq!(ids, ["1", "2"]);
+    /// c.verify(["1", "2"]);
+    /// ```
+    pub fn snapshot(&self) -> Vec<RecordView> {
+        self.thread
+            .with_actual(|actual| actual.iter().cloned().collect())
+    }
+
+    /// Takes the recorded [`call`] calls.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// let records = c.take();
+    /// assert_eq!(records.len(), 1);
+    /// assert!(c.is_empty());
+    /// ```
+    pub fn take(&mut self) -> Records {
+        self.thread.take_actual()
+    }
+
+    /// Return `Err` if [`call`] call does not match the expected pattern.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// call!("2");
+    ///
+    /// let e = c.check(["1", "3"]).unwrap_err();
+    /// assert_eq!(e.mismatch_index(), 1);
+    /// assert_eq!(e.actual_id(), "2");
+    /// assert_eq!(e.expect(), ["3"]);
+    /// ```
+    pub fn check(&mut self, expect: impl ToCall) -> Result<(), CallMismatchError> {
+        self.result_with_msg(expect, "mismatch call")
+    }
+
+    /// Return `Err` with specified message if [`call`] call does not match the expected pattern.
+    ///
+    /// Calling this method clears the recorded [`call`] calls.
+    fn result_with_msg(&mut self, expect: impl ToCall, msg: &str) -> Result<(), CallMismatchError> {
+        let expect: Call = expect.to_call();
+        let actual = self.thread.take_actual();
+        expect.verify(actual, msg)
+    }
+}
+impl<T: Thread> Default for CallRecorder<T> {
+    fn default() -> Self {
+        Self::new_raw()
+    }
+}
+impl<T: Thread> Drop for CallRecorder<T> {
+    fn drop(&mut self) {
+        if let Some(expect) = self.on_drop.take()
+            && !thread::panicking()
+        {
+            self.result_with_msg(expect, "mismatch call on drop")
+                .unwrap_or_panic();
+        }
+    }
+}
+
+/// Pattern of expected [`call`] calls.
+///
+/// To create a value of this type, call a method of this type or use [`ToCall`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Call {
+    Id(String),
+    Seq(VecDeque<Call>),
+    Par(Vec<Call>),
+    Any(Vec<Call>),
+    Repeat {
+        call: Box<Call>,
+        min: usize,
+        max: Option<usize>,
+    },
+    Optional(Box<Call>),
+    Glob(String),
+    #[cfg(feature = "regex")]
+    Regex(IdRegex),
+    Matches(IdPredicate),
+    IgnoreOthers(Box<Call>),
+    Not(Box<Call>),
+    Where(Box<Call>, Vec<Cond>),
+    CompletesWithin(TimeLimit),
+    Within(SpanCall),
+}
+
+impl Call {
+    /// Create `Call` to represent a single [`call`] call.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// c.verify(Call::id("1"));
+    /// ```
+    pub fn id(id: impl Display) -> Self {
+        Self::Id(id.to_string())
+    }
+
+    /// Create `Call` to represent a single [`call`] call whose ID matches the glob pattern.
+    ///
+    /// In the pattern, `*` matches any sequence of characters and `?` matches any single character.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("req-{}-done", 42);
+    /// c.verify(Call::glob("req-*-done"));
+    /// ```
+    pub fn glob(pattern: impl Display) -> Self {
+        Self::Glob(pattern.to_string())
+    }
+
+    /// Create `Call` to represent a single [`call`] call with any ID.
+    ///
+    /// Equivalent to `Call::glob("*")`.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// call!("2");
+    /// c.verify([Call::id("1"), Call::any_id()]);
+    /// ```
+    pub fn any_id() -> Self {
+        Self::glob("*")
+    }
+
+    /// Create `Call` to represent a single [`call`] call whose ID matches the regular expression.
+    ///
+    /// The regular expression matches anywhere in the ID unless anchored with `^` and `$`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regular expression.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("open(fd={})", 3);
+    /// c.verify(Call::regex(r"^open\(fd=\d+\)$"));
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Self {
+        match regex::Regex::new(pattern) {
+            Ok(re) => Self::Regex(IdRegex(re)),
+            Err(e) => panic!("invalid regex pattern `{pattern}` : {e}"),
+        }
+    }
+
+    /// Create `Call` to represent a single [`call`] call whose ID satisfies the predicate.
+    ///
+    /// `name` is displayed as the expected call when the call does not match.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("port-{}", 8080);
+    /// c.verify(Call::matches("port-(number)", |id| {
+    ///     id.strip_prefix("port-").is_some_and(|n| n.parse::<u16>().is_ok())
+    /// }));
+    /// ```
+    pub fn matches(name: impl Display, f: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        Self::Matches(IdPredicate {
+            name: name.to_string(),
+            f: Arc::new(f),
+        })
+    }
+
+    /// Create `Call` to represent no [`call`] call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assert_call::{Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// c.verify(Call::empty());
+    /// ```
+    pub fn empty() -> Self {
+        Self::Seq(VecDeque::new())
+    }
+
+    /// Create `Call` to represent all specified `Call`s will be called in sequence.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// call!("2");
+    /// c.verify(Call::seq(["1", "2"]));
+    /// ```
+    pub fn seq(p: impl IntoIterator<Item = impl ToCall>) -> Self {
+        Self::Seq(p.into_iter().map(|x| x.to_call()).collect())
+    }
+
+    /// Create `Call` to represent all specified `Call`s will be called in parallel.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("a-1");
+    /// call!("b-1");
+    /// call!("b-2");
+    /// call!("a-2");
+    /// c.verify(Call::par([["a-1", "a-2"], ["b-1", "b-2"]]));
+    /// ```
+    pub fn par(p: impl IntoIterator<Item = impl ToCall>) -> Self {
+        Self::Par(p.into_iter().map(|x| x.to_call()).collect())
+    }
+
+    /// Create `Call` to represent one of the specified `Call`s will be called.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// c.verify(Call::any(["1", "2"]));
+    /// call!("4");
+    /// c.verify(Call::any(["3", "4"]));
+    /// ```
+    pub fn any(p: impl IntoIterator<Item = impl ToCall>) -> Self {
+        Self::Any(p.into_iter().map(|x| x.to_call()).collect())
+    }
+
+    /// Create `Call` to represent the specified `Call` will be called exactly `n` times in sequence.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("poll");
+    /// call!("poll");
+    /// call!("poll");
+    /// c.verify(Call::times("poll", 3));
+    /// ```
+    pub fn times(p: impl ToCall, n: usize) -> Self {
+        Self::between(p, n..=n)
+    }
+
+    /// Create `Call` to represent the specified `Call` will be called `n` or more times in sequence.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("retry");
+    /// call!("retry");
+    /// call!("done");
+    /// c.verify([Call::at_least("retry", 1), Call::id("done")]);
+    /// ```
+    pub fn at_least(p: impl ToCall, n: usize) -> Self {
+        Self::between(p, n..)
+    }
+
+    /// Create `Call` to represent the specified `Call` will be called in sequence a number of times within `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is empty.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("poll");
+    /// call!("poll");
+    /// call!("poll");
+    /// c.verify(Call::between("poll", 3..=5));
+    /// ```
+    pub fn between(p: impl ToCall, range: impl RangeBounds<usize>) -> Self {
+        let min = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(&n) => Some(n),
+            Bound::Excluded(&n) => Some(n.checked_sub(1).expect("range is empty")),
+            Bound::Unbounded => None,
+        };
+        assert!(max.is_none_or(|max| min <= max), "range is empty");
+        Self::Repeat {
+            call: Box::new(p.to_call()),
+            min,
+            max,
+        }
+    }
+
+    /// Create `Call` to represent the specified `Call` will be called zero or more times in sequence.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// c.verify(Call::repeat("1"));
+    /// call!("1");
+    /// call!("1");
+    /// c.verify(Call::repeat("1"));
+    /// ```
+    pub fn repeat(p: impl ToCall) -> Self {
+        Self::between(p, ..)
+    }
+
+    /// Create `Call` to represent the specified `Call` may or may not be called.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let expect = [Call::id("open"), Call::optional("retry"), Call::id("close")];
+    /// call!("open");
+    /// call!("close");
+    /// c.verify(&expect);
+    /// call!("open");
+    /// call!("retry");
+    /// call!("close");
+    /// c.verify(&expect);
+    /// ```
+    pub fn optional(p: impl ToCall) -> Self {
+        Self::Optional(Box::new(p.to_call()))
+    }
+
+    /// Create `Call` to represent the specified `Call` will be called, ignoring other calls before, between and after them.
+    ///
+    /// Ignored calls are marked with `-` in the list of actual calls when the verification fails.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("log");
+    /// call!("1");
+    /// call!("log");
+    /// call!("2");
+    /// c.verify(Call::ignore_others(["1", "2"]));
+    /// ```
+    pub fn ignore_others(p: impl ToCall) -> Self {
+        Self::IgnoreOthers(Box::new(p.to_call()))
+    }
+
+    /// Create `Call` to represent a single [`call`] call that does not match the specified `Call`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("db.begin");
+    /// call!("db.commit");
+    /// c.verify(Call::repeat(Call::not("db.rollback")));
+    /// ```
+    pub fn not(p: impl ToCall) -> Self {
+        Self::Not(Box::new(p.to_call()))
+    }
+
+    /// Create `Call` to represent the calls of this `Call` have the field with the specified value.
+    ///
+    /// Fields are recorded by [`call`] macro in the form of `call!(id; key = value, ...)`.
+    /// Values are compared by their [`Debug`] representation.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let n = 1024;
+    /// call!("write"; fd = 3, len = n);
+    /// c.verify(Call::id("write").with("fd", 3).with("len", 1024));
+    /// ```
+    pub fn with(self, key: &str, value: impl Debug) -> Self {
+        self.where_(Cond::Field(key.to_string(), format!("{value:?}")))
+    }
+
+    /// Create `Call` to represent the calls of this `Call` are recorded by [`call_result`] and returned `Ok`.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call_result, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let _ = call_result!("fetch", "1".parse::<u32>());
+    /// c.verify(Call::id("fetch").returns_ok());
+    /// ```
+    pub fn returns_ok(self) -> Self {
+        self.where_(Cond::ReturnsOk)
+    }
+
+    /// Create `Call` to represent the calls of this `Call` are recorded by [`call_result`] and returned `Err`.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call_result, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let _ = call_result!("fetch", "a".parse::<u32>());
+    /// c.verify(Call::id("fetch").returns_err());
+    /// ```
+    pub fn returns_err(self) -> Self {
+        self.where_(Cond::ReturnsErr)
+    }
+
+    /// Create `Call` to represent the calls of this `Call` are recorded at least `delay` after the previous call.
+    ///
+    /// The previous call is the call recorded just before by the same [`CallRecorder`], regardless of the pattern.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    /// use std::{thread::sleep, time::Duration};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("try");
+    /// sleep(Duration::from_millis(20));
+    /// call!("retry");
+    /// c.verify([
+    ///     Call::id("try"),
+    ///     Call::id("retry").after_delay(Duration::from_millis(10)),
+    /// ]);
+    /// ```
+    pub fn after_delay(self, delay: Duration) -> Self {
+        self.where_(Cond::AfterDelay(delay))
+    }
+
+    /// Create `Call` to represent all calls of this `Call` are recorded within `limit` from the first call.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    /// use std::time::Duration;
+    ///
+    /// let mut c = CallRecorder::new();
+    /// call!("1");
+    /// call!("2");
+    /// c.verify(Call::seq(["1", "2"]).completes_within(Duration::from_secs(10)));
+    /// ```
+    pub fn completes_within(self, limit: Duration) -> Self {
+        Self::CompletesWithin(TimeLimit {
+            call: Box::new(self),
+            limit,
+            start: None,
+        })
+    }
+
+    fn where_(self, condition: Cond) -> Self {
+        match self {
+            Self::Where(call, mut conditions) => {
+                conditions.push(condition);
+                Self::Where(call, conditions)
+            }
+            call => Self::Where(Box::new(call), vec![condition]),
+        }
+    }
+
+    /// Create `Call` to represent the specified `Call` will be called inside the span created by [`call_span`].
+    ///
+    /// Only the calls recorded while the span is open in the same thread,
+    /// or in the same future wrapped by [`CallRecorder::scope`], are inside the span.
+    /// Calls recorded in other threads while the span is open are mismatches.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, call_span, Call, CallRecorder};
+    ///
+    /// let mut c = CallRecorder::new();
+    /// {
+    ///     let _s = call_span!("A");
+    ///     call!("B");
+    /// }
+    /// c.verify(Call::within("A", "B"));
+    /// ```
+    pub fn within(id: impl Display, p: impl ToCall) -> Self {
+        Self::Within(SpanCall {
+            id: id.to_string(),
+            call: Box::new(p.to_call()),
+            enter: None,
+        })
+    }
+
+    /// Create `Call` to represent the specified `Call` will be called on the specified thread.
+    ///
+    /// `thread` is a [`ThreadId`] or a thread name.
+    /// Calls matched by `p` on other threads are treated as mismatches.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "record", doc = "```")]
+    #[cfg_attr(not(feature = "record"), doc = "```ignore")]
+    /// use assert_call::{call, Call, CallRecorder};
+    /// use std::thread;
+    ///
+    /// let mut c = CallRecorder::new();
+    /// let h = thread::spawn(|| call!("work"));
+    /// let worker = h.thread().id();
+    /// h.join().unwrap();
+    /// c.verify(Call::on_thread(worker, "work"));
+    /// ```
+    pub fn on_thread(thread: impl Into<ThreadTag>, p: impl ToCall) -> Self {
+        p.to_call().where_(Cond::Thread(thread.into()))
+    }
+
+    fn verify(mut self, actual: Records, msg: &str) -> Result<(), CallMismatchError> {
+        let mut ignored = Vec::new();
+        match self.verify_nexts(&actual.0, &mut ignored) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e.finish(actual, ignored, msg)),
+        }
+    }
+    fn verify_prefix(mut self, actual: &mut Records, msg: &str) -> Result<(), CallMismatchError> {
+        let mut ignored = Vec::new();
+        let mut len = actual.0.len();
+        for (index, a) in actual.0.iter().enumerate() {
+            match self.next(Some(a)) {
+                Ok(true) => {}
+                Ok(false) => ignored.push(index),
+                Err(e) if e.is_end => {
+                    len = index;
+                    break;
+                }
+                Err(e) => {
+                    let e = CallMismatchError::new(e, index);
+                    return Err(e.finish(take(actual), ignored, msg));
+                }
+            }
+        }
+        if len == actual.0.len()
+            && let Err(e) = self.verify_next(len, None)
+        {
+            return Err(e.finish(take(actual), ignored, msg));
+        }
+        actual.0.drain(..len);
+        Ok(())
+    }
+    fn verify_not_found(&self, actual: &mut Records, msg: &str) -> Result<(), CallMismatchError> {
+        if let Some(index) = self.find(&actual.0) {
+            let e = NextError::new(vec![format!("not({})", self.expects())], false);
+            let e = CallMismatchError::new(e, index);
+            Err(e.finish(take(actual), Vec::new(), msg))
+        } else {
+            Ok(())
+        }
+    }
+    fn verify_nexts(
+        &mut self,
+        actual: &[Record],
+        ignored: &mut Vec<usize>,
+    ) -> Result<(), CallMismatchError> {
+        for index in 0..=actual.len() {
+            if !self.verify_next(index, actual.get(index))? {
+                ignored.push(index);
+            }
+        }
+        Ok(())
+    }
+    fn verify_next(&mut self, index: usize, a: Option<&Record>) -> Result<bool, CallMismatchError> {
+        match self.next(a) {
+            Ok(is_match) => Ok(is_match),
+            Err(e) if a.is_none() && e.is_end => Ok(true),
+            Err(e) if e.is_end && e.is_ignorable => Ok(false),
+            Err(e) => Err(CallMismatchError::new(e, index)),
+        }
+    }
+
+    /// Returns the index of the first call where a part of `actual` matching this pattern starts.
+    fn find(&self, actual: &[Record]) -> Option<usize> {
+        (0..actual.len()).find(|&index| self.match_len(&actual[index..]).is_some())
+    }
+
+    /// Returns the number of non-overlapping parts of `actual` that match this pattern.
+    fn count(&self, actual: &[Record]) -> usize {
+        let mut count = 0;
+        let mut index = 0;
+        while index < actual.len() {
+            if let Some(len) = self.match_len(&actual[index..]) {
+                count += 1;
+                index += len;
+            } else {
+                index += 1;
+            }
+        }
+        count
+    }
+
+    /// Returns the length of the shortest non-empty prefix of `actual` that matches this pattern.
+    fn match_len(&self, actual: &[Record]) -> Option<usize> {
+        let mut c = self.clone();
+        for (index, a) in actual.iter().enumerate() {
+            if !matches!(c.next(Some(a)), Ok(true)) {
+                return None;
+            }
+            if c.is_end() {
+                return Some(index + 1);
+            }
+        }
+        None
+    }
+
+    /// Returns `true` if the pattern is allowed to end here.
+    fn is_end(&self) -> bool {
+        matches!(self.clone().next(None), Err(e) if e.is_end)
+    }
+
+    /// Returns the IDs of the calls that can be accepted first.
+    fn expects(&self) -> String {
+        match self.clone().next(None) {
+            Ok(_) => String::new(),
+            Err(mut e) => {
+                e.expect.sort();
+                e.expect.dedup();
+                e.expect.join(", ")
+            }
+        }
+    }
+
+    /// Consume one call.
+    ///
+    /// Returns `Ok(true)` if the call was matched, `Ok(false)` if the call was ignored by [`Call::IgnoreOthers`].
+    /// If `Err` is returned, `self` is left unchanged.
+    fn next(&mut self, p: Option<&Record>) -> Result<bool, NextError> {
+        match self {
+            Call::Id(id) => {
+                if p.is_some_and(|x| x.is_call() && x.id == *id) {
+                    *self = Call::Seq(VecDeque::new());
+                    Ok(true)
+                } else {
+                    Err(NextError::new(vec![id.to_string()], false))
+                }
+            }
+            Call::Glob(pattern) => {
+                if p.is_some_and(|x| x.is_call() && glob_match(pattern, &x.id)) {
+                    *self = Call::Seq(VecDeque::new());
+                    Ok(true)
+                } else {
+                    Err(NextError::new(vec![pattern.to_string()], false))
+                }
+            }
+            #[cfg(feature = "regex")]
+            Call::Regex(re) => {
+                if p.is_some_and(|x| x.is_call() && re.0.is_match(&x.id)) {
+                    *self = Call::Seq(VecDeque::new());
+                    Ok(true)
+                } else {
+                    Err(NextError::new(vec![re.0.as_str().to_string()], false))
+                }
+            }
+            Call::Matches(m) => {
+                if p.is_some_and(|x| x.is_call() && (m.f)(&x.id)) {
+                    *self = Call::Seq(VecDeque::new());
+                    Ok(true)
+                } else {
+                    Err(NextError::new(vec![m.name.clone()], false))
+                }
+            }
+            Call::Seq(list) => {
+                let mut es = NextError::new(Vec::new(), true);
+                for index in 0..list.len() {
+                    let mut call = list[index].clone();
+                    match call.next(p) {
+                        Ok(is_match) => {
+                            if list[index].is_end() {
+                                // The call may also belong to the following patterns.
+                                let mut skip =
+                                    Call::Seq(list.range(index + 1..).cloned().collect());
+                                if let Ok(skip_is_match) = skip.next(p) {
+                                    list[index] = call;
+                                    list.drain(..index);
+                                    *self = Call::Any(vec![replace(self, Call::empty()), skip]);
+                                    return Ok(is_match || skip_is_match);
+                                }
+                            }
+                            list[index] = call;
+                            list.drain(..index);
+                            return Ok(is_match);
+                        }
+                        Err(e) => {
+                            let is_end = e.is_end;
+                            es.append(e);
+                            if !is_end {
+                                es.is_end = false;
+                                return Err(es);
+                            }
+                        }
+                    }
+                }
+                Err(es)
+            }
+            Call::Par(s) => {
+                let mut es = NextError::new(Vec::new(), true);
+                let mut is_ignored = false;
+                for i in s.iter_mut() {
+                    match i.next(p) {
+                        Ok(true) => return Ok(true),
+                        Ok(false) => is_ignored = true,
+                        Err(e) => {
+                            es.is_end &= e.is_end;
+                            es.append(e);
+                        }
+                    }
+                }
+                if is_ignored { Ok(false) } else { Err(es) }
+            }
+            Call::Any(s) => {
+                let results: Vec<_> = s.iter_mut().map(|s| s.next(p)).collect();
+                if results.iter().any(|r| r.is_ok()) {
+                    let is_match = results.iter().any(|r| matches!(r, Ok(true)));
+                    let mut results = results.iter();
+                    s.retain(|_| results.next().unwrap().is_ok());
+                    let branches = take(s);
+                    for call in branches {
+                        match call {
+                            Call::Any(branches) => s.extend(branches),
+                            call => s.push(call),
+                        }
+                    }
+                    if s.len() == 1 {
+                        *self = s.pop().unwrap();
+                    }
+                    Ok(is_match)
+                } else {
+                    let mut es = NextError::new(Vec::new(), false);
+                    for e in results.into_iter().filter_map(Result::err) {
+                        es.is_end |= e.is_end;
+                        es.append(e);
+                    }
+                    Err(es)
+                }
+            }
+            Call::Repeat { call, min, max } => {
+                if *max == Some(0) {
+                    return Err(NextError::new(Vec::new(), true));
+                }
+                let mut first = call.as_ref().clone();
+                match first.next(p) {
+                    Ok(is_match) => {
+                        let rest = Call::Repeat {
+                            call: call.clone(),
+                            min: min.saturating_sub(1),
+                            max: max.map(|max| max - 1),
+                        };
+                        *self = Call::Seq(VecDeque::from([first, rest]));
+                        Ok(is_match)
+                    }
+                    Err(mut e) => {
+                        e.is_end |= *min == 0;
+                        Err(e)
+                    }
+                }
+            }
+            Call::Optional(call) => match call.next(p) {
+                Ok(is_match) => {
+                    let call = replace(call.as_mut(), Call::empty());
+                    *self = call;
+                    Ok(is_match)
+                }
+                Err(mut e) => {
+                    e.is_end = true;
+                    Err(e)
+                }
+            },
+            Call::Not(call) => {
+                if p.is_some_and(|x| call.match_len(slice::from_ref(x)).is_none()) {
+                    *self = Call::Seq(VecDeque::new());
+                    Ok(true)
+                } else {
+                    Err(NextError::new(
+                        vec![format!("not({})", call.expects())],
+                        false,
+                    ))
+                }
+            }
+            Call::Where(call, conditions) => {
+                let diffs: Vec<_> = conditions.iter().filter_map(|c| c.diff(p?)).collect();
+                let r = if diffs.is_empty() || call.clone().next(p).is_err() {
+                    call.next(p)
+                } else {
+                    match call.clone().next(None) {
+                        Ok(_) => Err(NextError::new(Vec::new(), false)),
+                        Err(mut e) => {
+                            e.diffs = diffs;
+                            e.is_timed =
+                                conditions.iter().any(|c| matches!(c, Cond::AfterDelay(_)));
+                            Err(e)
+                        }
+                    }
+                };
+                r.map_err(|mut e| {
+                    let conditions: Vec<_> = conditions.iter().map(|c| c.to_string()).collect();
+                    let conditions = conditions.join(", ");
+                    for expect in &mut e.expect {
+                        *expect = format!("{expect} {{ {conditions} }}");
+                    }
+                    e
+                })
+            }
+            Call::CompletesWithin(TimeLimit { call, limit, start }) => {
+                let mut c = call.as_ref().clone();
+                match c.next(p) {
+                    Ok(true) => {
+                        let p = p.unwrap();
+                        let first = start.unwrap_or(p.time);
+                        let elapsed = p.time.saturating_duration_since(first);
+                        if elapsed > *limit {
+                            let mut e = match call.clone().next(None) {
+                                Ok(_) => NextError::new(Vec::new(), true),
+                                Err(e) => e,
+                            };
+                            for expect in &mut e.expect {
+                                *expect = format!("{expect} {{ within {limit:?} }}");
+                            }
+                            e.diffs
+                                .push(format!("elapsed : actual {elapsed:?}, expect <= {limit:?}"));
+                            e.is_timed = true;
+                            return Err(e);
+                        }
+                        **call = c;
+                        *start = Some(first);
+                        Ok(true)
+                    }
+                    r => {
+                        if r.is_ok() {
+                            **call = c;
+                        }
+                        r
+                    }
+                }
+            }
+            Call::Within(SpanCall { id, call, enter }) => {
+                let Some(enter) = *enter else {
+                    if let Some(p) = p
+                        && p.kind == RecordKind::Enter
+                        && p.id == *id
+                    {
+                        *enter = Some(p.seq);
+                        return Ok(true);
+                    }
+                    return Err(NextError::new(vec![format!("> {id}")], false));
+                };
+                let is_inside = p.is_some_and(|p| p.spans.contains(&enter));
+                let is_exit =
+                    p.is_some_and(|p| p.kind == RecordKind::Exit && p.spans.last() == Some(&enter));
+                let mut e = if is_exit {
+                    match call.clone().next(None) {
+                        Err(e) if !e.is_end => e,
+                        _ => {
+                            *self = Call::empty();
+                            return Ok(true);
+                        }
+                    }
+                } else if is_inside {
+                    match call.next(p) {
+                        Err(e) if e.is_end => e,
+                        r => return r,
+                    }
+                } else {
+                    let mut e = match call.clone().next(None) {
+                        Ok(_) => NextError::new(Vec::new(), true),
+                        Err(e) => e,
+                    };
+                    if let Some(p) = p
+                        && matches!(call.clone().next(Some(p)), Ok(true))
+                    {
+                        e.diffs
+                            .push(format!("span : actual (outside), expect {id}"));
+                    }
+                    e
+                };
+                if e.is_end {
+                    e.expect.push(format!("< {id}"));
+                    e.is_end = false;
+                }
+                Err(e)
+            }
+            Call::IgnoreOthers(call) => match call.next(p) {
+                Ok(is_match) => Ok(is_match),
+                Err(e) if e.is_end && p.is_some() => {
+                    // Let the following patterns accept the call.
+                    let mut e = NextError::new(Vec::new(), true);
+                    e.is_ignorable = true;
+                    Err(e)
+                }
+                Err(_) if p.is_some() => Ok(false),
+                Err(e) => Err(e),
+            },
+        }
+    }
+}
+
+/// Regular expression used by [`Call::Regex`].
+///
+/// Two values are equal if their source patterns are equal.
+#[cfg(feature = "regex")]
+#[derive(Clone, Debug)]
+pub struct IdRegex(regex::Regex);
+
+#[cfg(feature = "regex")]
+impl IdRegex {
+    /// Returns the source pattern of this regular expression.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+#[cfg(feature = "regex")]
+impl PartialEq for IdRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
     }
+}
+#[cfg(feature = "regex")]
+impl Eq for IdRegex {}
 
-    /// Panic if [`call`] call does not match the expected pattern.
-    ///
-    /// Calling this method clears the recorded [`call`] calls.
-    #[track_caller]
-    pub fn verify(&mut self, expect: impl ToCall) {
-        self.verify_with_msg(expect, "mismatch call");
+#[cfg(feature = "regex")]
+impl Hash for IdRegex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
     }
+}
 
-    /// Panic with specified message if [`call`] call does not match the expected pattern.
-    ///
-    /// Calling this method clears the recorded [`call`] calls.
-    #[track_caller]
-    pub fn verify_with_msg(&mut self, expect: impl ToCall, msg: &str) {
-        match self.result_with_msg(expect, msg) {
-            Ok(_) => {}
-            Err(e) => {
-                panic!("{:#}", e.display(true, Condition::tty_and_color()));
-            }
-        }
+/// Pattern inside a span used by [`Call::Within`].
+///
+/// Created by [`Call::within`].
+/// Two values are equal if the span IDs and the patterns are equal, regardless of the progress of matching.
+#[derive(Clone, Debug)]
+pub struct SpanCall {
+    id: String,
+    call: Box<Call>,
+    enter: Option<u64>,
+}
+impl SpanCall {
+    /// Returns the ID of the span.
+    pub fn id(&self) -> &str {
+        &self.id
     }
 
-    /// Return `Err` with specified message if [`call`] call does not match the expected pattern.
-    ///
-    /// Calling this method clears the recorded [`call`] calls.
-    fn result_with_msg(&mut self, expect: impl ToCall, msg: &str) -> Result<(), CallMismatchError> {
-        let expect: Call = expect.to_call();
-        let actual = self.thread.take_actual();
-        expect.verify(actual, msg)
+    /// Returns the pattern that must be called inside the span.
+    pub fn call(&self) -> &Call {
+        &self.call
     }
 }
-impl<T: Thread> Default for CallRecorder<T> {
-    fn default() -> Self {
-        Self::new_raw()
+impl PartialEq for SpanCall {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.call == other.call
     }
 }
-impl<T: Thread> Drop for CallRecorder<T> {
-    fn drop(&mut self) {}
+impl Eq for SpanCall {}
+
+impl Hash for SpanCall {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.call.hash(state);
+    }
 }
 
-/// Pattern of expected [`call`] calls.
+/// Pattern with a time limit used by [`Call::CompletesWithin`].
 ///
-/// To create a value of this type, call a method of this type or use [`ToCall`].
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub enum Call {
-    Id(String),
-    Seq(VecDeque<Call>),
-    Par(Vec<Call>),
-    Any(Vec<Call>),
+/// Created by [`Call::completes_within`].
+/// Two values are equal if the patterns and the limits are equal, regardless of the progress of matching.
+#[derive(Clone, Debug)]
+pub struct TimeLimit {
+    call: Box<Call>,
+    limit: Duration,
+    start: Option<Instant>,
 }
-
-impl Call {
-    /// Create `Call` to represent a single [`call`] call.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use assert_call::{call, Call, CallRecorder};
-    ///
-    /// let mut c = CallRecorder::new();
-    /// call!("1");
-    /// c.verify(Call::id("1"));
-    /// ```
-    pub fn id(id: impl Display) -> Self {
-        Self::Id(id.to_string())
+impl TimeLimit {
+    /// Returns the pattern that must complete within the limit.
+    pub fn call(&self) -> &Call {
+        &self.call
     }
 
-    /// Create `Call` to represent no [`call`] call.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use assert_call::{Call, CallRecorder};
-    ///
-    /// let mut c = CallRecorder::new();
-    /// c.verify(Call::empty());
-    /// ```
-    pub fn empty() -> Self {
-        Self::Seq(VecDeque::new())
+    /// Returns the limit.
+    pub fn limit(&self) -> Duration {
+        self.limit
     }
+}
+impl PartialEq for TimeLimit {
+    fn eq(&self, other: &Self) -> bool {
+        self.call == other.call && self.limit == other.limit
+    }
+}
+impl Eq for TimeLimit {}
 
-    /// Create `Call` to represent all specified `Call`s will be called in sequence.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use assert_call::{call, Call, CallRecorder};
-    ///
-    /// let mut c = CallRecorder::new();
-    /// call!("1");
-    /// call!("2");
-    /// c.verify(Call::seq(["1", "2"]));
-    /// ```
-    pub fn seq(p: impl IntoIterator<Item = impl ToCall>) -> Self {
-        Self::Seq(p.into_iter().map(|x| x.to_call()).collect())
+impl Hash for TimeLimit {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.call.hash(state);
+        self.limit.hash(state);
     }
+}
 
-    /// Create `Call` to represent all specified `Call`s will be called in parallel.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use assert_call::{call, Call, CallRecorder};
-    ///
-    /// let mut c = CallRecorder::new();
-    /// call!("a-1");
-    /// call!("b-1");
-    /// call!("b-2");
-    /// call!("a-2");
-    /// c.verify(Call::par([["a-1", "a-2"], ["b-1", "b-2"]]));
-    /// ```
-    pub fn par(p: impl IntoIterator<Item = impl ToCall>) -> Self {
-        Self::Par(p.into_iter().map(|x| x.to_call()).collect())
+/// Predicate used by [`Call::Matches`].
+///
+/// Two values are equal only if they are clones of the same value created by [`Call::matches`].
+#[derive(Clone)]
+pub struct IdPredicate {
+    name: String,
+    f: Arc<dyn Fn(&str) -> bool + Send + Sync>,
+}
+impl IdPredicate {
+    /// Returns the name displayed as the expected call.
+    pub fn name(&self) -> &str {
+        &self.name
     }
+}
+impl fmt::Debug for IdPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IdPredicate").field(&self.name).finish()
+    }
+}
+impl PartialEq for IdPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.f, &other.f)
+    }
+}
+impl Eq for IdPredicate {}
 
-    /// Create `Call` to represent one of the specified `Call`s will be called.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use assert_call::{call, Call, CallRecorder};
-    ///
-    /// let mut c = CallRecorder::new();
-    /// call!("1");
-    /// c.verify(Call::any(["1", "2"]));
-    /// call!("4");
-    /// c.verify(Call::any(["3", "4"]));
-    /// ```
-    pub fn any(p: impl IntoIterator<Item = impl ToCall>) -> Self {
-        Self::Any(p.into_iter().map(|x| x.to_call()).collect())
+impl Hash for IdPredicate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.f).cast::<()>().hash(state);
     }
+}
 
-    fn verify(mut self, actual: Records, msg: &str) -> Result<(), CallMismatchError> {
-        match self.verify_nexts(&actual.0) {
-            Ok(_) => Ok(()),
-            Err(mut e) => {
-                e.actual = actual;
-                e.expect.sort();
-                e.expect.dedup();
-                e.msg = msg.to_string();
-                Err(e)
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let mut p_index = 0;
+    let mut s_index = 0;
+    let mut backtrack = None;
+    while s_index < s.len() {
+        match pattern.get(p_index) {
+            Some('*') => {
+                backtrack = Some((p_index, s_index));
+                p_index += 1;
+                continue;
+            }
+            Some(&c) if c == '?' || c == s[s_index] => {
+                p_index += 1;
+                s_index += 1;
+                continue;
             }
+            _ => {}
         }
-    }
-    fn verify_nexts(&mut self, actual: &[Record]) -> Result<(), CallMismatchError> {
-        for index in 0..=actual.len() {
-            self.verify_next(index, actual.get(index))?;
+        if let Some((p_star, s_star)) = backtrack {
+            p_index = p_star + 1;
+            s_index = s_star + 1;
+            backtrack = Some((p_star, s_star + 1));
+        } else {
+            return false;
         }
-        Ok(())
     }
-    fn verify_next(&mut self, index: usize, a: Option<&Record>) -> Result<(), CallMismatchError> {
-        if let Err(e) = self.next(a) {
-            if a.is_none() && e.is_empty() {
-                return Ok(());
-            }
-            Err(CallMismatchError::new(e, index))
-        } else {
-            Ok(())
+    pattern[p_index..].iter().all(|&c| c == '*')
+}
+
+/// Result of [`Call::next`] when the call could not be accepted.
+#[derive(Debug)]
+struct NextError {
+    /// IDs of the calls that could have been accepted.
+    expect: Vec<String>,
+    /// Differences of the fields between the actual call and the expected calls.
+    diffs: Vec<String>,
+    /// `true` if the pattern is allowed to end here.
+    is_end: bool,
+    /// `true` if the call can be ignored when no following pattern accepts it,
+    /// because a finished [`Call::IgnoreOthers`] is at the end of the pattern.
+    is_ignorable: bool,
+    /// `true` if a timing condition caused the mismatch.
+    is_timed: bool,
+}
+impl NextError {
+    fn new(expect: Vec<String>, is_end: bool) -> Self {
+        Self {
+            expect,
+            diffs: Vec::new(),
+            is_end,
+            is_ignorable: false,
+            is_timed: false,
         }
     }
+    fn append(&mut self, mut e: NextError) {
+        self.expect.append(&mut e.expect);
+        self.diffs.append(&mut e.diffs);
+        self.is_ignorable |= e.is_ignorable;
+        self.is_timed |= e.is_timed;
+    }
+}
 
-    fn next(&mut self, p: Option<&Record>) -> Result<(), Vec<String>> {
-        match self {
-            Call::Id(id) => {
-                if Some(id.as_str()) == p.as_ref().map(|x| x.id.as_str()) {
-                    *self = Call::Seq(VecDeque::new());
-                    Ok(())
-                } else {
-                    Err(vec![id.to_string()])
+/// Condition on a single [`call`] call used by [`Call::Where`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Cond {
+    /// The call has the field with the value. Created by [`Call::with`].
+    Field(String, String),
+    /// The call returned `Ok`. Created by [`Call::returns_ok`].
+    ReturnsOk,
+    /// The call returned `Err`. Created by [`Call::returns_err`].
+    ReturnsErr,
+    /// The call was recorded at least the duration after the previous call. Created by [`Call::after_delay`].
+    AfterDelay(Duration),
+    /// The call was recorded on the thread. Created by [`Call::on_thread`].
+    Thread(ThreadTag),
+}
+impl Cond {
+    /// Returns the description of the difference if `r` does not satisfy this condition.
+    fn diff(&self, r: &Record) -> Option<String> {
+        let (name, actual, expect) = match self {
+            Cond::Field(key, value) => {
+                let actual = r.field(key);
+                if actual == Some(value) {
+                    return None;
                 }
+                (
+                    key.as_str(),
+                    actual.unwrap_or("(none)").to_string(),
+                    value.clone(),
+                )
             }
-            Call::Seq(list) => {
-                while !list.is_empty() {
-                    match list[0].next(p) {
-                        Err(e) if e.is_empty() => list.pop_front(),
-                        ret => return ret,
-                    };
+            Cond::ReturnsOk | Cond::ReturnsErr => {
+                let is_ok = matches!(self, Cond::ReturnsOk);
+                if r.outcome.as_ref().is_some_and(|o| o.is_ok() == is_ok) {
+                    return None;
                 }
-                Err(Vec::new())
+                let expect = if is_ok { "Ok" } else { "Err" };
+                ("returns", r.fmt_outcome(), expect.to_string())
             }
-            Call::Par(s) => {
-                let mut es = Vec::new();
-                for i in s.iter_mut() {
-                    match i.next(p) {
-                        Ok(_) => return Ok(()),
-                        Err(mut e) => es.append(&mut e),
-                    }
+            Cond::AfterDelay(delay) => {
+                if r.delay.is_some_and(|d| d >= *delay) {
+                    return None;
                 }
-                Err(es)
+                let actual = r.delay.map_or("(none)".to_string(), |d| format!("{d:?}"));
+                ("delay", actual, format!(">= {delay:?}"))
             }
-            Call::Any(s) => {
-                let mut is_end = false;
-                let mut is_ok = false;
-                let mut es = Vec::new();
-                s.retain_mut(|s| match s.next(p) {
-                    Ok(_) => {
-                        is_ok = true;
-                        true
-                    }
-                    Err(e) => {
-                        is_end |= e.is_empty();
-                        es.extend(e);
-                        false
-                    }
-                });
-                if is_ok {
-                    Ok(())
-                } else if is_end {
-                    Err(Vec::new())
-                } else {
-                    Err(es)
+            Cond::Thread(thread) => {
+                if thread.is_match(r) {
+                    return None;
                 }
+                ("thread", r.fmt_thread(), thread.to_string())
             }
+        };
+        Some(format!("{name} : actual {actual}, expect {expect}"))
+    }
+}
+impl Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cond::Field(key, value) => write!(f, "{key} = {value}"),
+            Cond::ReturnsOk => write!(f, "returns Ok"),
+            Cond::ReturnsErr => write!(f, "returns Err"),
+            Cond::AfterDelay(delay) => write!(f, "after {delay:?}"),
+            Cond::Thread(thread) => write!(f, "on {thread}"),
+        }
+    }
+}
+
+/// Thread specified by [`Call::on_thread`] and [`CallRecorder::verify_per_thread`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ThreadTag {
+    /// The thread with the ID.
+    Id(ThreadId),
+    /// The thread with the name.
+    Name(String),
+}
+impl ThreadTag {
+    fn is_match(&self, r: &Record) -> bool {
+        match self {
+            ThreadTag::Id(id) => r.thread_id == *id,
+            ThreadTag::Name(name) => r.thread_name.as_ref() == Some(name),
+        }
+    }
+}
+impl From<ThreadId> for ThreadTag {
+    fn from(id: ThreadId) -> Self {
+        ThreadTag::Id(id)
+    }
+}
+impl From<&str> for ThreadTag {
+    fn from(name: &str) -> Self {
+        ThreadTag::Name(name.to_string())
+    }
+}
+impl From<String> for ThreadTag {
+    fn from(name: String) -> Self {
+        ThreadTag::Name(name)
+    }
+}
+impl Display for ThreadTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThreadTag::Id(id) => write!(f, "{id:?}"),
+            ThreadTag::Name(name) => write!(f, "{name}"),
         }
     }
 }
@@ -394,39 +2084,130 @@ impl ToCall for () {
 }
 
 /// The error type representing that the call to [`call`] is different from what was expected.
+///
+/// Returned by [`CallRecorder::check`].
+#[derive(Debug)]
+pub struct CallMismatchError(Box<CallMismatchErrorData>);
+
 #[derive(Debug)]
-struct CallMismatchError {
+struct CallMismatchErrorData {
     msg: String,
     actual: Records,
     expect: Vec<String>,
+    diffs: Vec<String>,
     mismatch_index: usize,
+    ignored: Vec<usize>,
     thread_id: ThreadId,
+    thread_name: Option<String>,
+    waited: Option<Duration>,
+    is_timed: bool,
 }
 impl CallMismatchError {
-    fn new(expect: Vec<String>, mismatch_index: usize) -> Self {
-        Self {
+    fn new(e: NextError, mismatch_index: usize) -> Self {
+        Self(Box::new(CallMismatchErrorData {
             msg: String::new(),
             actual: Records::empty(),
-            expect,
+            expect: e.expect,
+            diffs: e.diffs,
             mismatch_index,
+            ignored: Vec::new(),
             thread_id: thread::current().id(),
-        }
+            thread_name: thread::current().name().map(|s| s.to_string()),
+            waited: None,
+            is_timed: e.is_timed,
+        }))
     }
 
-    fn actual_id(&self, index: usize) -> &str {
-        if let Some(a) = self.actual.0.get(index) {
-            &a.id
-        } else {
-            "(end)"
-        }
+    fn finish(mut self, actual: Records, ignored: Vec<usize>, msg: &str) -> Self {
+        self.0.actual = actual;
+        self.0.ignored = ignored;
+        self.0.expect.sort();
+        self.0.expect.dedup();
+        self.0.diffs.sort();
+        self.0.diffs.dedup();
+        self.0.msg = msg.to_string();
+        self
+    }
+
+    /// Index of the first [`call`] call that did not match the expected pattern.
+    ///
+    /// If the recorded calls ended before the expected pattern was satisfied,
+    /// this is equal to the number of recorded calls.
+    pub fn mismatch_index(&self) -> usize {
+        self.0.mismatch_index
+    }
+
+    /// ID of the mismatched call, or `"(end)"` if the recorded calls ended before the expected pattern was satisfied.
+    pub fn actual_id(&self) -> &str {
+        self.0.actual.id(self.0.mismatch_index)
+    }
+
+    /// IDs of all recorded calls.
+    pub fn actual_ids(&self) -> impl Iterator<Item = &str> {
+        self.0.actual.0.iter().map(|a| a.id.as_str())
+    }
+
+    /// IDs of the calls that were expected at the mismatched position.
+    pub fn expect(&self) -> &[String] {
+        &self.0.expect
+    }
+
+    /// Source file of the mismatched call.
+    ///
+    /// Returns `None` if the recorded calls ended before the expected pattern was satisfied.
+    pub fn file(&self) -> Option<&'static str> {
+        self.0.actual.0.get(self.0.mismatch_index).map(|a| a.file)
+    }
+
+    /// Source line of the mismatched call.
+    ///
+    /// Returns `None` if the recorded calls ended before the expected pattern was satisfied.
+    pub fn line(&self) -> Option<u32> {
+        self.0.actual.0.get(self.0.mismatch_index).map(|a| a.line)
+    }
+
+    /// Differences of the fields between the mismatched call and the expected calls.
+    ///
+    /// Each item has the form `"{key} : actual {actual}, expect {expect}"`.
+    pub fn field_diffs(&self) -> &[String] {
+        &self.0.diffs
+    }
+
+    /// Indexes of the calls ignored by [`Call::ignore_others`] before the mismatch.
+    pub fn ignored_indexes(&self) -> &[usize] {
+        &self.0.ignored
+    }
+
+    /// ID of the thread that performed the verification.
+    pub fn thread_id(&self) -> ThreadId {
+        self.0.thread_id
     }
-    #[cfg(test)]
+
+    /// Name of the thread that performed the verification.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.0.thread_name.as_deref()
+    }
+
+    /// Time waited for the calls by [`CallRecorder::verify_eventually`].
+    ///
+    /// Returns `None` if the verification did not wait.
+    pub fn waited(&self) -> Option<Duration> {
+        self.0.waited
+    }
+    #[cfg(all(test, feature = "record"))]
     fn set_dummy_file_line(&mut self) {
-        for a in &mut self.actual.0 {
+        for a in &mut self.0.actual.0 {
             a.set_dummy_file_line();
         }
     }
 
+    /// Returns an object that implements [`Display`] with options.
+    ///
+    /// If `backtrace` is `true`, the backtrace of each call is included if captured.
+    /// If `color` is `true`, the mismatched call is highlighted with ANSI color codes.
+    ///
+    /// The time elapsed since the first call is displayed for each call
+    /// only if the mismatch is caused by [`Call::completes_within`] or [`Call::after_delay`].
     pub fn display(&self, backtrace: bool, color: bool) -> impl Display + '_ {
         struct CallMismatchErrorDisplay<'a> {
             this: &'a CallMismatchError,
@@ -452,29 +2233,60 @@ impl CallMismatchError {
         color: bool,
     ) -> std::fmt::Result {
         let around = 5;
-        if backtrace && self.actual.has_bakctrace() {
+        if backtrace && self.0.actual.has_bakctrace() {
             writeln!(f, "actual calls with backtrace :")?;
-            self.actual.fmt_backtrace(f, self.mismatch_index, around)?;
+            self.0
+                .actual
+                .fmt_backtrace(f, self.0.mismatch_index, around)?;
             writeln!(f)?;
         }
 
         writeln!(f, "actual calls :")?;
-        self.actual
-            .fmt_summary(f, self.mismatch_index, around, color)?;
+        self.0.actual.fmt_summary(
+            f,
+            self.0.mismatch_index,
+            &self.0.ignored,
+            around,
+            self.0.is_timed,
+            color,
+        )?;
 
         writeln!(f)?;
-        writeln!(f, "{}", self.msg)?;
-        if let Some(a) = self.actual.0.get(self.mismatch_index) {
+        writeln!(f, "{}", self.0.msg)?;
+        if let Some(a) = self.0.actual.0.get(self.0.mismatch_index) {
             writeln!(f, "{}:{}", a.file, a.line)?;
         }
+        if let Some(waited) = self.0.waited {
+            writeln!(f, "waited : {waited:?}")?;
+        }
         if backtrace {
-            writeln!(f, "thread : {:?}", self.thread_id)?;
+            let thread = fmt_thread(self.0.thread_id, self.0.thread_name.as_deref());
+            writeln!(f, "thread : {thread}")?;
+        }
+        writeln!(f, "actual : {}", self.0.actual.label(self.0.mismatch_index))?;
+        if self.0.expect.is_empty() {
+            writeln!(f, "expect : (no calls expected)")?;
+        } else {
+            writeln!(f, "expect : {}", self.0.expect.join(", "))?;
+        }
+        for diff in &self.0.diffs {
+            writeln!(f, "field  : {diff}")?;
         }
-        writeln!(f, "actual : {}", self.actual_id(self.mismatch_index))?;
-        writeln!(f, "expect : {}", self.expect.join(", "))?;
         Ok(())
     }
 }
+trait UnwrapOrPanic {
+    fn unwrap_or_panic(self);
+}
+impl UnwrapOrPanic for Result<(), CallMismatchError> {
+    #[track_caller]
+    fn unwrap_or_panic(self) {
+        if let Err(e) = self {
+            panic!("{:#}", e.display(true, Condition::tty_and_color()));
+        }
+    }
+}
+
 impl Display for CallMismatchError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.fmt_with(f, false, false)
@@ -482,17 +2294,99 @@ impl Display for CallMismatchError {
 }
 impl Error for CallMismatchError {}
 
-/// Record of one [`call`] call.
+/// An error returned by [`CallRecorder::wait_for`] when no matching call is recorded within the timeout.
 #[derive(Debug)]
+pub struct TimeoutError {
+    expect: String,
+    timeout: Duration,
+}
+impl TimeoutError {
+    /// IDs of the calls that were waited for.
+    pub fn expect(&self) -> &str {
+        &self.expect
+    }
+
+    /// Time waited for the calls.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "timed out after {:?} waiting for {}",
+            self.timeout, self.expect
+        )
+    }
+}
+impl Error for TimeoutError {}
+
+fn fmt_thread(id: ThreadId, name: Option<&str>) -> String {
+    name.map_or_else(|| format!("{id:?}"), |name| name.to_string())
+}
+
+/// Record of one [`call`] call.
+#[derive(Debug, Clone)]
 struct Record {
     id: String,
     file: &'static str,
     line: u32,
-    backtrace: Backtrace,
+    kind: RecordKind,
+    depth: usize,
+    /// Sequence numbers of the enter records of the spans enclosing this record.
+    ///
+    /// For an exit record, the last item is the enter record of the exited span.
+    spans: Vec<u64>,
+    time: Instant,
+    delay: Option<Duration>,
+    fields: Vec<(&'static str, String)>,
+    outcome: Option<Result<String, String>>,
+    seq: u64,
+    backtrace: Arc<Backtrace>,
     thread_id: ThreadId,
+    thread_name: Option<String>,
 }
 impl Record {
-    #[cfg(test)]
+    fn is_call(&self) -> bool {
+        self.kind == RecordKind::Call
+    }
+    fn field(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    }
+    fn label(&self) -> String {
+        let mut s = match self.kind {
+            RecordKind::Call => self.id.clone(),
+            RecordKind::Enter => format!("> {}", self.id),
+            RecordKind::Exit => format!("< {}", self.id),
+        };
+        if !self.fields.is_empty() {
+            let fields: Vec<_> = self
+                .fields
+                .iter()
+                .map(|(k, v)| format!("{k} = {v}"))
+                .collect();
+            s += &format!(" {{ {} }}", fields.join(", "));
+        }
+        if self.outcome.is_some() {
+            s += &format!(" -> {}", self.fmt_outcome());
+        }
+        s
+    }
+    fn fmt_thread(&self) -> String {
+        fmt_thread(self.thread_id, self.thread_name.as_deref())
+    }
+    fn fmt_outcome(&self) -> String {
+        match &self.outcome {
+            Some(Ok(value)) => format!("Ok({value})"),
+            Some(Err(value)) => format!("Err({value})"),
+            None => "(none)".to_string(),
+        }
+    }
+    #[cfg(all(test, feature = "record"))]
     fn set_dummy_file_line(&mut self) {
         self.file = r"tests\test.rs";
         self.line = 10;
@@ -501,7 +2395,7 @@ impl Record {
 
 impl Display for Record {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "# {}", self.id)?;
+        writeln!(f, "# {}", self.label())?;
         writeln!(f, "{}:{}", self.file, self.line)?;
         if self.backtrace.status() == BacktraceStatus::Captured {
             writeln!(f)?;
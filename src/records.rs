@@ -2,27 +2,121 @@ use std::{
     backtrace::{Backtrace, BacktraceStatus},
     cell::RefCell,
     cmp::min,
-    fmt::{self, Formatter},
+    fmt::{self, Debug, Formatter},
     marker::PhantomData,
     mem::take,
-    sync::{Condvar, Mutex},
-    thread,
+    pin::Pin,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+    vec,
 };
 
 use yansi::{Condition, Paint};
 
-use crate::Record;
+use crate::{
+    Record,
+    clock::{Clock, SystemClock},
+};
 
 thread_local! {
-    static ACTUAL_LOCAL: RefCell<Option<Vec<Record>>> = const { RefCell::new(None) };
+    static ACTUAL_LOCAL: RefCell<Option<State>> = const { RefCell::new(None) };
+    static ACTUAL_TASK: RefCell<Option<TaskContext>> = const { RefCell::new(None) };
 }
 
-static ACTUAL_GLOBAL: Mutex<Option<Vec<Record>>> = Mutex::new(None);
+static ACTUAL_GLOBAL: Mutex<Option<State>> = Mutex::new(None);
 static ACTUAL_GLOBAL_CONDVAR: Condvar = Condvar::new();
+static ACTUAL_GLOBAL_PUSHED: Condvar = Condvar::new();
+
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// State of one `CallRecorder`.
+struct State {
+    records: Records,
+    clock: Arc<dyn Clock>,
+    last_time: Option<Instant>,
+}
+impl State {
+    fn new() -> Self {
+        Self {
+            records: Records::empty(),
+            clock: Arc::new(SystemClock),
+            last_time: None,
+        }
+    }
+    fn push(&mut self, mut record: Record) {
+        record.time = self.clock.now();
+        record.delay = self
+            .last_time
+            .map(|t| record.time.saturating_duration_since(t));
+        self.last_time = Some(record.time);
+        self.records.0.push(record);
+    }
+}
+
+/// Call `f` with the state of the current `CallRecorder`.
+fn with_current<R>(f: impl FnOnce(Option<&mut State>) -> R) -> R {
+    if let Some(task) = ACTUAL_TASK.with_borrow(|task| task.as_ref().map(|t| t.actual.clone())) {
+        let r = f(task.actual.lock().unwrap().as_mut());
+        task.pushed.notify_all();
+        return r;
+    }
+    ACTUAL_LOCAL.with(|actual| {
+        if let Some(actual) = &mut *actual.borrow_mut() {
+            f(Some(actual))
+        } else {
+            let r = f(ACTUAL_GLOBAL.lock().unwrap().as_mut());
+            ACTUAL_GLOBAL_PUSHED.notify_all();
+            r
+        }
+    })
+}
+
+/// Call `f` each time a call is recorded until `f` returns `Some` or `timeout` elapses.
+fn wait_pushed<R>(
+    actual: &Mutex<Option<State>>,
+    pushed: &Condvar,
+    timeout: Duration,
+    mut f: impl FnMut(&mut Records) -> Option<R>,
+) -> Option<R> {
+    // `None` if the deadline is too far to represent; wait without a deadline then.
+    let deadline = Instant::now().checked_add(timeout);
+    let mut actual = actual.lock().unwrap();
+    loop {
+        if let Some(r) = f(&mut actual.as_mut().unwrap().records) {
+            return Some(r);
+        }
+        if let Some(deadline) = deadline {
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            actual = pushed.wait_timeout(actual, deadline - now).unwrap().0;
+        } else {
+            actual = pushed.wait(actual).unwrap();
+        }
+    }
+}
 
 pub trait Thread {
     fn init() -> Self;
-    fn take_actual(&self) -> Records;
+    fn with_actual<R>(&self, f: impl FnOnce(&mut Records) -> R) -> R;
+    fn set_clock(&self, clock: Arc<dyn Clock>);
+    fn take_actual(&self) -> Records {
+        self.with_actual(take)
+    }
+    fn wait_actual<R>(
+        &self,
+        timeout: Duration,
+        f: impl FnMut(&mut Records) -> Option<R>,
+    ) -> Option<R> {
+        let _ = timeout;
+        self.with_actual(f)
+    }
 }
 
 pub struct Local(PhantomData<*mut ()>);
@@ -34,12 +128,15 @@ impl Thread for Local {
             if actual.is_some() {
                 panic!("CallRecorder::new_local() is already called in this thread");
             }
-            *actual = Some(Vec::new());
+            *actual = Some(State::new());
         });
         Self(PhantomData)
     }
-    fn take_actual(&self) -> Records {
-        Records(ACTUAL_LOCAL.with(|actual| take(actual.borrow_mut().as_mut().unwrap())))
+    fn with_actual<R>(&self, f: impl FnOnce(&mut Records) -> R) -> R {
+        ACTUAL_LOCAL.with(|actual| f(&mut actual.borrow_mut().as_mut().unwrap().records))
+    }
+    fn set_clock(&self, clock: Arc<dyn Clock>) {
+        ACTUAL_LOCAL.with(|actual| actual.borrow_mut().as_mut().unwrap().clock = clock)
     }
 }
 impl Drop for Local {
@@ -57,11 +154,21 @@ impl Thread for Global {
         while actual.is_some() {
             actual = ACTUAL_GLOBAL_CONDVAR.wait(actual).unwrap();
         }
-        *actual = Some(Vec::new());
+        *actual = Some(State::new());
         Self {}
     }
-    fn take_actual(&self) -> Records {
-        Records(take(ACTUAL_GLOBAL.lock().unwrap().as_mut().unwrap()))
+    fn with_actual<R>(&self, f: impl FnOnce(&mut Records) -> R) -> R {
+        f(&mut ACTUAL_GLOBAL.lock().unwrap().as_mut().unwrap().records)
+    }
+    fn set_clock(&self, clock: Arc<dyn Clock>) {
+        ACTUAL_GLOBAL.lock().unwrap().as_mut().unwrap().clock = clock;
+    }
+    fn wait_actual<R>(
+        &self,
+        timeout: Duration,
+        f: impl FnMut(&mut Records) -> Option<R>,
+    ) -> Option<R> {
+        wait_pushed(&ACTUAL_GLOBAL, &ACTUAL_GLOBAL_PUSHED, timeout, f)
     }
 }
 impl Drop for Global {
@@ -71,7 +178,213 @@ impl Drop for Global {
     }
 }
 
-#[derive(Debug)]
+pub struct Task(Arc<TaskState>);
+
+struct TaskState {
+    actual: Mutex<Option<State>>,
+    pushed: Condvar,
+}
+
+/// Recorder and spans of the [`Scope`] being polled.
+#[derive(Clone)]
+struct TaskContext {
+    actual: Arc<TaskState>,
+    spans: SpanStack,
+}
+
+impl Task {
+    pub(crate) fn scope<F: Future>(&self, future: F) -> Scope<F> {
+        Scope {
+            context: TaskContext {
+                actual: self.0.clone(),
+                spans: SpanStack::default(),
+            },
+            future: Some(Box::pin(future)),
+        }
+    }
+}
+impl Thread for Task {
+    fn init() -> Self {
+        Self(Arc::new(TaskState {
+            actual: Mutex::new(Some(State::new())),
+            pushed: Condvar::new(),
+        }))
+    }
+    fn with_actual<R>(&self, f: impl FnOnce(&mut Records) -> R) -> R {
+        f(&mut self.0.actual.lock().unwrap().as_mut().unwrap().records)
+    }
+    fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.0.actual.lock().unwrap().as_mut().unwrap().clock = clock;
+    }
+    fn wait_actual<R>(
+        &self,
+        timeout: Duration,
+        f: impl FnMut(&mut Records) -> Option<R>,
+    ) -> Option<R> {
+        wait_pushed(&self.0.actual, &self.0.pushed, timeout, f)
+    }
+}
+impl Drop for Task {
+    fn drop(&mut self) {
+        self.0.actual.lock().unwrap().take();
+    }
+}
+
+/// Future that records [`call`](crate::call) calls made while it is polled or dropped.
+///
+/// Created by [`CallRecorder::scope`](crate::CallRecorder::scope).
+pub struct Scope<F> {
+    context: TaskContext,
+    future: Option<Pin<Box<F>>>,
+}
+impl<F> Scope<F> {
+    fn enter(&self) -> impl Drop + use<F> {
+        struct Restore(Option<TaskContext>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                ACTUAL_TASK.set(self.0.take());
+            }
+        }
+        let context = self.context.clone();
+        Restore(ACTUAL_TASK.with_borrow_mut(|a| a.replace(context)))
+    }
+}
+
+impl<F: Future> Future for Scope<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let _restore = self.enter();
+        self.future.as_mut().unwrap().as_mut().poll(cx)
+    }
+}
+impl<F> Drop for Scope<F> {
+    fn drop(&mut self) {
+        let _restore = self.enter();
+        self.future.take();
+    }
+}
+
+/// Sequence numbers of the enter records of the spans that are not exited.
+///
+/// Each thread and each [`Scope`] has its own stack,
+/// so the depth of the spans follows a future even if it is polled on different threads.
+type SpanStack = Arc<Mutex<Vec<u64>>>;
+
+thread_local! {
+    static SPANS: SpanStack = SpanStack::default();
+}
+
+/// Returns the span stack of the current [`Scope`] or the current thread.
+fn current_spans() -> SpanStack {
+    ACTUAL_TASK
+        .with_borrow(|task| task.as_ref().map(|t| t.spans.clone()))
+        .unwrap_or_else(|| SPANS.with(|spans| spans.clone()))
+}
+
+impl Record {
+    fn new(id: String, file: &'static str, line: u32) -> Self {
+        let spans = current_spans().lock().unwrap().clone();
+        Self {
+            id,
+            file,
+            line,
+            kind: RecordKind::Call,
+            depth: spans.len(),
+            spans,
+            time: Instant::now(),
+            delay: None,
+            fields: Vec::new(),
+            outcome: None,
+            seq: NEXT_SEQ.fetch_add(1, Ordering::Relaxed),
+            backtrace: Arc::new(Backtrace::capture()),
+            thread_id: thread::current().id(),
+            thread_name: thread::current().name().map(|s| s.to_string()),
+        }
+    }
+}
+
+/// Kind of the record.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum RecordKind {
+    /// Recorded by [`call`](crate::call) and similar macros.
+    Call,
+    /// Recorded when a span created by [`call_span`](crate::call_span) is entered.
+    Enter,
+    /// Recorded when a span created by [`call_span`](crate::call_span) is exited.
+    Exit,
+}
+
+/// Guard of the span created by [`call_span`](crate::call_span).
+///
+/// Records the exit of the span when dropped, including on unwinding.
+#[cfg(feature = "record")]
+#[must_use]
+pub struct SpanGuard {
+    id: String,
+    file: &'static str,
+    line: u32,
+    seq: u64,
+    spans: SpanStack,
+}
+
+#[cfg(feature = "record")]
+impl SpanGuard {
+    #[track_caller]
+    pub fn enter(id: String, file: &'static str, line: u32) -> Self {
+        let mut record = Record::new(id.clone(), file, line);
+        record.kind = RecordKind::Enter;
+        let seq = Records::push_record(record);
+        let spans = current_spans();
+        spans.lock().unwrap().push(seq);
+        Self {
+            id,
+            file,
+            line,
+            seq,
+            spans,
+        }
+    }
+}
+#[cfg(feature = "record")]
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        // The guard may be dropped on another thread than the one that entered the span.
+        let mut spans = {
+            let mut spans = self.spans.lock().unwrap();
+            spans.retain(|&seq| seq != self.seq);
+            spans.clone()
+        };
+        let mut record = Record::new(take(&mut self.id), self.file, self.line);
+        record.kind = RecordKind::Exit;
+        record.depth = spans.len();
+        spans.push(self.seq);
+        record.spans = spans;
+        // Do not panic here to avoid a double panic when unwinding.
+        let _ = Records::try_push_record(record);
+    }
+}
+
+/// Guard of the span created by [`call_span`](crate::call_span).
+///
+/// Does nothing because the `record` feature is disabled.
+#[cfg(not(feature = "record"))]
+#[must_use]
+pub struct SpanGuard(());
+
+#[cfg(not(feature = "record"))]
+impl SpanGuard {
+    pub fn noop() -> Self {
+        Self(())
+    }
+}
+
+/// Key to add the result to the record created by [`call_result`](crate::call_result).
+#[derive(Clone, Copy, Debug)]
+pub struct ResultKey(u64);
+
+/// Recorded [`call`](crate::call) calls.
+#[derive(Debug, Default)]
 pub struct Records(pub(crate) Vec<Record>);
 
 impl Records {
@@ -79,34 +392,104 @@ impl Records {
         Self(Vec::new())
     }
 
+    /// Returns the number of recorded calls.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if no calls are recorded.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the recorded calls.
+    pub fn iter(&self) -> impl Iterator<Item = &RecordView> + '_ {
+        self.0.iter().map(RecordView::from_ref)
+    }
+
     #[track_caller]
     pub fn push(id: String, file: &'static str, line: u32) {
-        let record = Record {
-            id,
-            file,
-            line,
-            backtrace: Backtrace::capture(),
-            thread_id: thread::current().id(),
+        Self::push_record(Record::new(id, file, line));
+    }
+
+    #[track_caller]
+    pub fn push_with_fields(
+        id: String,
+        fields: Vec<(&'static str, String)>,
+        file: &'static str,
+        line: u32,
+    ) {
+        let mut record = Record::new(id, file, line);
+        record.fields = fields;
+        Self::push_record(record);
+    }
+
+    /// Returns `true` if a `CallRecorder` that records calls in the current thread is initialized.
+    pub fn is_recording() -> bool {
+        with_current(|actual| actual.is_some())
+    }
+
+    /// Push the record if `CallRecorder` is initialized, and do nothing otherwise.
+    pub fn try_push(id: String, file: &'static str, line: u32) {
+        let _ = Self::try_push_record(Record::new(id, file, line));
+    }
+
+    /// Push the record with fields if `CallRecorder` is initialized, and do nothing otherwise.
+    pub fn try_push_with_fields(
+        id: String,
+        fields: Vec<(&'static str, String)>,
+        file: &'static str,
+        line: u32,
+    ) {
+        let mut record = Record::new(id, file, line);
+        record.fields = fields;
+        let _ = Self::try_push_record(record);
+    }
+
+    #[track_caller]
+    pub fn push_result_entry(id: String, file: &'static str, line: u32) -> ResultKey {
+        ResultKey(Self::push_record(Record::new(id, file, line)))
+    }
+
+    pub fn set_result<T: Debug, E: Debug>(key: ResultKey, r: &Result<T, E>) {
+        let outcome = match r {
+            Ok(value) => Ok(format!("{value:?}")),
+            Err(e) => Err(format!("{e:?}")),
         };
-        if let Err(e) = ACTUAL_LOCAL.with(|actual| {
-            if let Some(actual) = &mut *actual.borrow_mut() {
+        with_current(|actual| {
+            if let Some(actual) = actual
+                && let Some(r) = actual.records.0.iter_mut().rev().find(|r| r.seq == key.0)
+            {
+                r.outcome = Some(outcome);
+            }
+        });
+    }
+
+    #[track_caller]
+    fn push_record(record: Record) -> u64 {
+        let seq = record.seq;
+        if let Err(record) = Self::try_push_record(record) {
+            let id = record.id;
+            let file = record.file;
+            let line = record.line;
+            panic!("`CallRecorder` is not initialized. (\"{id}\")\n{file}:{line}");
+        }
+        seq
+    }
+
+    /// Push the record, or return it if `CallRecorder` is not initialized.
+    fn try_push_record(record: Record) -> Result<(), Box<Record>> {
+        with_current(|actual| {
+            if let Some(actual) = actual {
                 actual.push(record);
                 Ok(())
-            } else if let Some(seq) = ACTUAL_GLOBAL.lock().unwrap().as_mut() {
-                seq.push(record);
-                Ok(())
             } else {
-                let id = record.id;
-                Err(format!(
-                    "`CallRecorder` is not initialized. (\"{id}\")\n{file}:{line}"
-                ))
+                Err(Box::new(record))
             }
-        }) {
-            panic!("{e}");
-        }
+        })
     }
 
-    fn id(&self, index: usize) -> &str {
+    pub(crate) fn id(&self, index: usize) -> &str {
         if let Some(a) = self.0.get(index) {
             &a.id
         } else {
@@ -114,11 +497,22 @@ impl Records {
         }
     }
 
+    /// Returns the call ID with fields.
+    pub(crate) fn label(&self, index: usize) -> String {
+        if let Some(a) = self.0.get(index) {
+            a.label()
+        } else {
+            "(end)".to_string()
+        }
+    }
+
     pub(crate) fn fmt_summary(
         &self,
         f: &mut Formatter,
         mismatch_index: usize,
+        ignored: &[usize],
         around: usize,
+        time: bool,
         color: bool,
     ) -> fmt::Result {
         let mut start = 0;
@@ -130,11 +524,23 @@ impl Records {
         if start > 0 {
             writeln!(f, "  ...(previous {start} calls omitted)")?;
         }
+        let multi_thread = self.0.iter().any(|r| r.thread_id != self.0[0].thread_id);
         for index in start..end {
-            self.fmt_item_summary(f, mismatch_index == index, self.id(index), color)?;
+            let is_ignored = ignored.binary_search(&index).is_ok();
+            let r = &self.0[index];
+            let indent = "  ".repeat(r.depth);
+            let mut label = format!("{indent}{}", r.label());
+            if multi_thread {
+                label += &format!(" [{}]", r.fmt_thread());
+            }
+            if time {
+                let elapsed = r.time.saturating_duration_since(self.0[0].time);
+                label += &format!(" (+{elapsed:?})");
+            }
+            self.fmt_item_summary(f, mismatch_index == index, is_ignored, &label, color)?;
         }
         if end == self.0.len() {
-            self.fmt_item_summary(f, mismatch_index == self.0.len(), "(end)", color)?;
+            self.fmt_item_summary(f, mismatch_index == self.0.len(), false, "(end)", color)?;
         } else {
             writeln!(f, "  ...(following {} calls omitted)", self.0.len() - end)?;
         }
@@ -144,10 +550,17 @@ impl Records {
         &self,
         f: &mut Formatter,
         is_mismatch: bool,
+        is_ignored: bool,
         id: &str,
         color: bool,
     ) -> fmt::Result {
-        let head = if is_mismatch { "*" } else { " " };
+        let head = if is_mismatch {
+            "*"
+        } else if is_ignored {
+            "-"
+        } else {
+            " "
+        };
         let cond = if is_mismatch && color {
             Condition::ALWAYS
         } else {
@@ -172,9 +585,9 @@ impl Records {
         }
         for index in start..end {
             let r = &self.0[index];
-            writeln!(f, "# {}", r.id)?;
+            writeln!(f, "# {}", r.label())?;
             writeln!(f, "{}:{}", r.file, r.line)?;
-            writeln!(f, "thread: {:?}", r.thread_id)?;
+            writeln!(f, "thread: {}", r.fmt_thread())?;
             writeln!(f, "{}", r.backtrace)?;
         }
 
@@ -192,3 +605,115 @@ impl Records {
             .any(|r| r.backtrace.status() == BacktraceStatus::Captured)
     }
 }
+
+impl IntoIterator for Records {
+    type Item = RecordView;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self.0.into_iter())
+    }
+}
+
+/// Iterator over the recorded calls returned by [`Records::into_iter`].
+pub struct IntoIter(vec::IntoIter<Record>);
+
+impl Iterator for IntoIter {
+    type Item = RecordView;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(RecordView)
+    }
+}
+
+/// Read-only view of one recorded [`call`](crate::call) call.
+#[derive(Debug, Clone)]
+#[repr(transparent)]
+pub struct RecordView(Record);
+
+impl RecordView {
+    fn from_ref(r: &Record) -> &Self {
+        // SAFETY: `RecordView` is `#[repr(transparent)]` over `Record`.
+        unsafe { &*(r as *const Record as *const Self) }
+    }
+
+    /// Returns the call ID.
+    pub fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    /// Returns the source file where [`call`](crate::call) was called.
+    pub fn file(&self) -> &'static str {
+        self.0.file
+    }
+
+    /// Returns the source line where [`call`](crate::call) was called.
+    pub fn line(&self) -> u32 {
+        self.0.line
+    }
+
+    /// Returns the value of the field with the specified key.
+    ///
+    /// The value is the [`Debug`] representation of the value specified in [`call`](crate::call).
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.0.field(key)
+    }
+
+    /// Returns the fields in the order specified in [`call`](crate::call).
+    pub fn fields(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        self.0.fields.iter().map(|(k, v)| (*k, v.as_str()))
+    }
+
+    /// Returns the kind of the record.
+    pub fn kind(&self) -> RecordKind {
+        self.0.kind
+    }
+
+    /// Returns the number of spans created by [`call_span`](crate::call_span) enclosing this record
+    /// in the same thread, or in the same future wrapped by [`CallRecorder::scope`](crate::CallRecorder::scope).
+    pub fn depth(&self) -> usize {
+        self.0.depth
+    }
+
+    /// Returns the result of the expression recorded by [`call_result`](crate::call_result).
+    ///
+    /// The value is the [`Debug`] representation of the `Ok` or `Err` value.
+    /// Returns `None` if the call was not recorded by `call_result`, or the expression has not been completed.
+    pub fn outcome(&self) -> Option<Result<&str, &str>> {
+        self.0
+            .outcome
+            .as_ref()
+            .map(|o| o.as_ref().map(|v| v.as_str()).map_err(|e| e.as_str()))
+    }
+
+    /// Returns the time when the call was recorded.
+    pub fn time(&self) -> Instant {
+        self.0.time
+    }
+
+    /// Returns the time elapsed since the previous call was recorded by the same `CallRecorder`.
+    ///
+    /// Returns `None` if this is the first call recorded by the `CallRecorder`.
+    pub fn delay(&self) -> Option<Duration> {
+        self.0.delay
+    }
+
+    /// Returns the ID of the thread where [`call`](crate::call) was called.
+    pub fn thread_id(&self) -> ThreadId {
+        self.0.thread_id
+    }
+
+    /// Returns the name of the thread where [`call`](crate::call) was called.
+    ///
+    /// Returns `None` if the thread is unnamed.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.0.thread_name.as_deref()
+    }
+
+    /// Returns the backtrace captured when [`call`](crate::call) was called.
+    ///
+    /// The backtrace is captured by [`Backtrace::capture`].
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.0.backtrace
+    }
+}
@@ -1,6 +1,8 @@
 use pretty_assertions::assert_str_eq;
 
-use crate::{call, records::Thread, Call, CallRecorder, ToCall};
+use crate::{
+    call, call_result, call_span, glob_match, records::Thread, Call, CallRecorder, ToCall,
+};
 
 #[test]
 fn err() {
@@ -21,6 +23,27 @@ expect : 1"#,
     );
 }
 
+#[test]
+fn err_no_calls_expected() {
+    let c = CallRecorder::new_local();
+    call!("0");
+    call!("1");
+    assert_err(
+        c,
+        ["0"],
+        r#"
+actual calls :
+  0
+* 1
+  (end)
+
+(message)
+tests\test.rs:10
+actual : 1
+expect : (no calls expected)"#,
+    );
+}
+
 #[test]
 fn err_many_expect() {
     let c = CallRecorder::new_local();
@@ -76,6 +99,203 @@ expect : 10"#,
     );
 }
 
+#[test]
+fn err_repeat() {
+    let c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    assert_err(
+        c,
+        [Call::at_least("1", 1), Call::id("3")],
+        r#"
+actual calls :
+  1
+* 2
+  (end)
+
+(message)
+tests\test.rs:10
+actual : 2
+expect : 1, 3"#,
+    );
+}
+
+#[test]
+fn err_optional() {
+    let c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    assert_err(
+        c,
+        [Call::id("1"), Call::optional("3"), Call::id("4")],
+        r#"
+actual calls :
+  1
+* 2
+  (end)
+
+(message)
+tests\test.rs:10
+actual : 2
+expect : 3, 4"#,
+    );
+}
+
+#[test]
+fn err_glob() {
+    let c = CallRecorder::new_local();
+    call!("req-1-fail");
+    assert_err(
+        c,
+        Call::glob("req-*-done"),
+        r#"
+actual calls :
+* req-1-fail
+  (end)
+
+(message)
+tests\test.rs:10
+actual : req-1-fail
+expect : req-*-done"#,
+    );
+}
+
+#[test]
+fn glob() {
+    assert!(glob_match("", ""));
+    assert!(glob_match("*", ""));
+    assert!(glob_match("*", "abc"));
+    assert!(glob_match("a?c", "abc"));
+    assert!(glob_match("a*c", "ac"));
+    assert!(glob_match("a*c", "abcbc"));
+    assert!(glob_match("*-*-done", "req-1-done"));
+    assert!(!glob_match("a?c", "ac"));
+    assert!(!glob_match("a*c", "abcb"));
+    assert!(!glob_match("abc", "ab"));
+    assert!(!glob_match("", "a"));
+}
+
+#[test]
+fn err_ignore_others() {
+    let c = CallRecorder::new_local();
+    call!("1");
+    call!("log");
+    call!("2");
+    call!("log");
+    assert_err(
+        c,
+        Call::ignore_others(["1", "3"]),
+        r#"
+actual calls :
+  1
+- log
+- 2
+- log
+* (end)
+
+(message)
+actual : (end)
+expect : 3"#,
+    );
+}
+
+#[test]
+fn err_not() {
+    let c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    assert_err(
+        c,
+        Call::times(Call::not(Call::any(["2", "3"])), 2),
+        r#"
+actual calls :
+  1
+* 2
+  (end)
+
+(message)
+tests\test.rs:10
+actual : 2
+expect : not(2, 3)"#,
+    );
+}
+
+#[test]
+fn err_fields() {
+    let c = CallRecorder::new_local();
+    call!("open"; path = "a.txt");
+    call!("write"; fd = 4, len = 1024);
+    assert_err(
+        c,
+        [
+            Call::id("open"),
+            Call::id("write").with("fd", 3).with("len", 1024),
+        ],
+        r#"
+actual calls :
+  open { path = "a.txt" }
+* write { fd = 4, len = 1024 }
+  (end)
+
+(message)
+tests\test.rs:10
+actual : write { fd = 4, len = 1024 }
+expect : write { fd = 3, len = 1024 }
+field  : fd : actual 4, expect 3"#,
+    );
+}
+
+#[test]
+fn err_returns() {
+    let c = CallRecorder::new_local();
+    let _ = call_result!("fetch", "1".parse::<u32>());
+    assert_err(
+        c,
+        Call::id("fetch").returns_err(),
+        r#"
+actual calls :
+* fetch -> Ok(1)
+  (end)
+
+(message)
+tests\test.rs:10
+actual : fetch -> Ok(1)
+expect : fetch { returns Err }
+field  : returns : actual Ok(1), expect Err"#,
+    );
+}
+
+#[test]
+fn err_span() {
+    let c = CallRecorder::new_local();
+    {
+        let _s = call_span!("a");
+        call!("1");
+        {
+            let _s = call_span!("b");
+            call!("2");
+        }
+    }
+    assert_err(
+        c,
+        Call::within("a", ["1", "2"]),
+        r#"
+actual calls :
+  > a
+    1
+*   > b
+      2
+    < b
+  < a
+  (end)
+
+(message)
+tests\test.rs:10
+actual : > b
+expect : 2"#,
+    );
+}
+
 fn assert_err(mut c: CallRecorder<impl Thread>, expect: impl ToCall, expect_display: &str) {
     match c.result_with_msg(expect, "(message)") {
         Ok(_) => panic!("no error."),
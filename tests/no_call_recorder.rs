@@ -1,3 +1,5 @@
+#![cfg(feature = "record")]
+
 use assert_call::call;
 
 // Use a file containing only a single test,
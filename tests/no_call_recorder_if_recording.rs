@@ -0,0 +1,13 @@
+use assert_call::call_if_recording;
+
+// Use a file containing only a single test,
+// as multiple tests in a file can cause multiple tests to run simultaneously in the same process and initialize `CallRecorder` with other tests
+#[test]
+fn no_call_recorder_if_recording() {
+    fn value() -> u32 {
+        panic!("evaluated");
+    }
+    call_if_recording!("0");
+    call_if_recording!("{}", value());
+    call_if_recording!("1"; value = value());
+}
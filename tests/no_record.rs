@@ -0,0 +1,28 @@
+#![cfg(not(feature = "record"))]
+use std::{
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use assert_call::{call, call_if_recording, call_result, call_span, future::RecordCallsExt};
+
+fn value() -> u32 {
+    panic!("evaluated");
+}
+
+#[test]
+fn no_record() {
+    call!("0");
+    call!("{}", value());
+    call!("1"; value = value());
+    call_if_recording!("{}", value());
+    let _s = call_span!("{}", value());
+    assert_eq!(call_result!(value(), Ok::<u32, ()>(1)), Ok(1));
+}
+
+#[test]
+fn no_record_future() {
+    let mut f = pin!(async { 1 }.record_calls("f"));
+    let mut cx = Context::from_waker(Waker::noop());
+    assert_eq!(f.as_mut().poll(&mut cx), Poll::Ready(1));
+}
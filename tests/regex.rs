@@ -0,0 +1,25 @@
+#![cfg(all(feature = "regex", feature = "record"))]
+
+use assert_call::{call, Call, CallRecorder};
+
+#[test]
+fn regex() {
+    let mut c = CallRecorder::new_local();
+    call!("open(fd={})", 3);
+    call!("read(fd={}, len={})", 3, 1024);
+    c.verify([Call::regex(r"^open\(fd=\d+\)$"), Call::regex(r"len=\d+")]);
+}
+
+#[test]
+fn regex_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("open(fd=x)");
+    let e = c.check(Call::regex(r"^open\(fd=\d+\)$")).unwrap_err();
+    assert_eq!(e.expect(), [r"^open\(fd=\d+\)$"]);
+}
+
+#[should_panic]
+#[test]
+fn regex_invalid() {
+    Call::regex("(");
+}
@@ -0,0 +1,227 @@
+#![cfg(feature = "record")]
+
+use std::{
+    pin::{pin, Pin},
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, spawn, Thread},
+};
+
+use assert_call::{call, call_span, future::RecordCallsExt, Call, CallRecorder};
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(value) => return value,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn new_task() {
+    let mut c = CallRecorder::new_task();
+    block_on(c.scope(async {
+        call!("1");
+        YieldNow(false).await;
+        call!("2");
+    }));
+    c.verify(["1", "2"]);
+}
+
+#[test]
+fn new_task_concurrent() {
+    let mut c1 = CallRecorder::new_task();
+    let mut c2 = CallRecorder::new_task();
+    let mut f1 = pin!(c1.scope(async {
+        call!("a1");
+        YieldNow(false).await;
+        call!("a2");
+    }));
+    let mut f2 = pin!(c2.scope(async {
+        call!("b1");
+        YieldNow(false).await;
+        call!("b2");
+    }));
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(f1.as_mut().poll(&mut cx).is_pending());
+    assert!(f2.as_mut().poll(&mut cx).is_pending());
+    assert!(f1.as_mut().poll(&mut cx).is_ready());
+    assert!(f2.as_mut().poll(&mut cx).is_ready());
+    c1.verify(["a1", "a2"]);
+    c2.verify(["b1", "b2"]);
+}
+
+#[test]
+fn new_task_move_thread() {
+    let mut c = CallRecorder::new_task();
+    let mut f = Box::pin(c.scope(async {
+        call!("1");
+        YieldNow(false).await;
+        call!("2");
+    }));
+    assert!(
+        f.as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+            .is_pending()
+    );
+    spawn(move || {
+        assert!(
+            f.as_mut()
+                .poll(&mut Context::from_waker(Waker::noop()))
+                .is_ready()
+        );
+    })
+    .join()
+    .unwrap();
+    c.verify(["1", "2"]);
+}
+
+#[test]
+fn new_task_with_local() {
+    let mut l = CallRecorder::new_local();
+    let mut c = CallRecorder::new_task();
+    call!("local-1");
+    block_on(c.scope(async { call!("task") }));
+    call!("local-2");
+    c.verify("task");
+    l.verify(["local-1", "local-2"]);
+}
+
+#[test]
+fn new_task_nested() {
+    let mut c1 = CallRecorder::new_task();
+    let mut c2 = CallRecorder::new_task();
+    block_on(c1.scope(async {
+        call!("1");
+        c2.scope(async { call!("2") }).await;
+        call!("3");
+    }));
+    c1.verify(["1", "3"]);
+    c2.verify("2");
+}
+
+#[test]
+fn record_calls() {
+    let mut c = CallRecorder::new_task();
+    block_on(
+        c.scope(
+            async {
+                call!("1");
+                YieldNow(false).await;
+                call!("2");
+            }
+            .record_calls("f"),
+        ),
+    );
+    c.verify(["f:poll", "1", "2", "f:ready"]);
+}
+
+#[test]
+fn record_calls_drop() {
+    let mut c = CallRecorder::new_task();
+    let mut f = Box::pin(
+        c.scope(
+            async {
+                call!("1");
+                YieldNow(false).await;
+                call!("2");
+            }
+            .record_calls("f"),
+        ),
+    );
+    assert!(
+        f.as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+            .is_pending()
+    );
+    drop(f);
+    c.verify(["f:poll", "1", "f:drop"]);
+}
+
+#[test]
+fn record_calls_drop_before_poll() {
+    let mut c = CallRecorder::new_local();
+    drop(async {}.record_calls("f"));
+    c.verify("f:drop");
+}
+
+#[test]
+fn record_calls_cancel() {
+    let mut c = CallRecorder::new_task();
+    block_on(c.scope(async {
+        let mut f = pin!(YieldNow(false).record_calls("f"));
+        assert!(
+            f.as_mut()
+                .poll(&mut Context::from_waker(Waker::noop()))
+                .is_pending()
+        );
+        call!("cancel");
+    }));
+    c.verify(["f:poll", "cancel", "f:drop"]);
+}
+
+#[test]
+fn record_calls_poll_after_ready() {
+    let mut c = CallRecorder::new_local();
+    let mut f = pin!(YieldNow(true).record_calls("f"));
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(f.as_mut().poll(&mut cx).is_ready());
+    let _ = f.as_mut().poll(&mut cx);
+    c.verify(["f:poll", "f:ready"]);
+}
+
+#[test]
+fn call_span_move_thread() {
+    let mut l = CallRecorder::new_local();
+    let mut c = CallRecorder::new_task();
+    let mut f = Box::pin(c.scope(async {
+        let _s = call_span!("a");
+        YieldNow(false).await;
+        call!("in");
+    }));
+    assert!(
+        f.as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+            .is_pending()
+    );
+    spawn(move || {
+        assert!(
+            f.as_mut()
+                .poll(&mut Context::from_waker(Waker::noop()))
+                .is_ready()
+        );
+    })
+    .join()
+    .unwrap();
+    call!("out");
+    let s = c.snapshot();
+    assert_eq!(s.iter().map(|r| r.depth()).collect::<Vec<_>>(), [0, 1, 0]);
+    c.verify(Call::within("a", "in"));
+    assert_eq!(l.snapshot()[0].depth(), 0);
+    l.verify("out");
+}
@@ -1,12 +1,18 @@
+#![cfg(feature = "record")]
+
 use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
     sync::atomic::{AtomicUsize, Ordering},
-    thread::{scope, sleep, spawn},
+    thread::{current, scope, sleep, spawn, Builder},
     time::Duration,
 };
 
 use pretty_assertions::assert_eq;
 
-use assert_call::{call, Call, CallRecorder};
+use assert_call::{
+    call, call_if_recording, call_result, call_span, clock::ManualClock, records::RecordKind, Call,
+    CallRecorder, ThreadTag,
+};
 
 #[test]
 fn new() {
@@ -232,3 +238,1084 @@ fn call_format() {
     call!("b_{}", x);
     c.verify(["a_10", "b_10"]);
 }
+
+#[test]
+fn check() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    let line = line!() + 1;
+    call!("2");
+    let e = c.check(["1", "3"]).unwrap_err();
+    assert_eq!(e.mismatch_index(), 1);
+    assert_eq!(e.actual_id(), "2");
+    assert_eq!(e.actual_ids().collect::<Vec<_>>(), ["1", "2"]);
+    assert_eq!(e.expect(), ["3"]);
+    assert_eq!(e.file(), Some(file!()));
+    assert_eq!(e.line(), Some(line));
+
+    call!("1");
+    assert!(c.check("1").is_ok());
+}
+
+#[test]
+fn check_end() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    let e = c.check(["1", "2"]).unwrap_err();
+    assert_eq!(e.mismatch_index(), 1);
+    assert_eq!(e.actual_id(), "(end)");
+    assert_eq!(e.file(), None);
+    assert_eq!(e.line(), None);
+}
+
+#[test]
+fn times() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("1");
+    call!("2");
+    c.verify([Call::times("1", 2), Call::id("2")]);
+}
+
+#[should_panic]
+#[test]
+fn times_fail_too_few() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    c.verify([Call::times("1", 2), Call::id("2")]);
+}
+
+#[should_panic]
+#[test]
+fn times_fail_too_many() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("1");
+    call!("1");
+    c.verify(Call::times("1", 2));
+}
+
+#[test]
+fn between() {
+    let mut c = CallRecorder::new_local();
+    let expect = Call::between("1", 1..=2);
+    call!("1");
+    c.verify(&expect);
+    call!("1");
+    call!("1");
+    c.verify(&expect);
+    call!("1");
+    call!("1");
+    call!("1");
+    assert!(c.check(&expect).is_err());
+    assert!(c.check(&expect).is_err());
+}
+
+#[test]
+fn at_least() {
+    let mut c = CallRecorder::new_local();
+    let expect = [Call::at_least(["a", "b"], 1), Call::id("c")];
+    call!("a");
+    call!("b");
+    call!("c");
+    c.verify(&expect);
+    call!("a");
+    call!("b");
+    call!("a");
+    call!("b");
+    call!("c");
+    c.verify(&expect);
+    call!("c");
+    assert!(c.check(&expect).is_err());
+}
+
+#[test]
+fn repeat() {
+    let mut c = CallRecorder::new_local();
+    let expect = Call::repeat("1");
+    c.verify(&expect);
+    call!("1");
+    call!("1");
+    c.verify(&expect);
+}
+
+#[test]
+fn repeat_par() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    call!("b");
+    call!("a");
+    call!("b");
+    call!("b");
+    c.verify(Call::par([Call::times("a", 2), Call::times("b", 3)]));
+}
+
+#[test]
+fn repeat_any() {
+    let mut c = CallRecorder::new_local();
+    let expect = Call::any([Call::times("a", 2), Call::times("b", 1)]);
+    call!("a");
+    call!("a");
+    c.verify(&expect);
+    call!("b");
+    c.verify(&expect);
+}
+
+#[should_panic]
+#[test]
+fn between_empty_range() {
+    Call::between("1", 2..2);
+}
+
+#[test]
+fn optional() {
+    let mut c = CallRecorder::new_local();
+    let expect = [Call::id("1"), Call::optional(["2", "3"]), Call::id("4")];
+    call!("1");
+    call!("4");
+    c.verify(&expect);
+    call!("1");
+    call!("2");
+    call!("3");
+    call!("4");
+    c.verify(&expect);
+}
+
+#[test]
+fn optional_end() {
+    let mut c = CallRecorder::new_local();
+    let expect = [Call::id("1"), Call::optional("2")];
+    call!("1");
+    c.verify(&expect);
+    call!("1");
+    call!("2");
+    c.verify(&expect);
+}
+
+#[should_panic]
+#[test]
+fn optional_fail_partial() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    call!("4");
+    c.verify([Call::id("1"), Call::optional(["2", "3"]), Call::id("4")]);
+}
+
+#[test]
+fn glob() {
+    let mut c = CallRecorder::new_local();
+    call!("req-{}-done", 42);
+    call!("req-{}-fail", 43);
+    c.verify([Call::glob("req-*-done"), Call::glob("req-??-*")]);
+}
+
+#[should_panic]
+#[test]
+fn glob_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("req-42-fail");
+    c.verify(Call::glob("req-*-done"));
+}
+
+#[test]
+fn any_id() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("{}", 2);
+    c.verify(Call::times(Call::any_id(), 2));
+}
+
+#[test]
+fn matches() {
+    let mut c = CallRecorder::new_local();
+    call!("port-8080");
+    call!("port-abc");
+    let e = c
+        .check(Call::times(
+            Call::matches("port-(number)", |id| {
+                id.strip_prefix("port-")
+                    .is_some_and(|n| n.parse::<u16>().is_ok())
+            }),
+            2,
+        ))
+        .unwrap_err();
+    assert_eq!(e.mismatch_index(), 1);
+    assert_eq!(e.expect(), ["port-(number)"]);
+}
+
+#[test]
+fn matches_eq() {
+    let a = Call::matches("a", |_| true);
+    let b = Call::matches("a", |_| true);
+    assert_eq!(a, a.clone());
+    assert_ne!(a, b);
+}
+
+#[test]
+fn verify_contains() {
+    let mut c = CallRecorder::new_local();
+    call!("log");
+    call!("1");
+    call!("log");
+    call!("2");
+    call!("log");
+    c.verify_contains(["1", "2"]);
+}
+
+#[should_panic]
+#[test]
+fn verify_contains_fail_order() {
+    let mut c = CallRecorder::new_local();
+    call!("2");
+    call!("1");
+    c.verify_contains(["1", "2"]);
+}
+
+#[test]
+fn ignore_others_par() {
+    let mut c = CallRecorder::new_local();
+    call!("a-1");
+    call!("log");
+    call!("b-1");
+    call!("a-2");
+    c.verify(Call::ignore_others(Call::par(["a-1", "b-1"])));
+}
+
+#[test]
+fn ignore_others_in_seq() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("log");
+    call!("2");
+    call!("3");
+    c.verify([Call::id("1"), Call::ignore_others("2")]);
+}
+
+#[test]
+fn any_in_par() {
+    let mut c = CallRecorder::new_local();
+    call!("c");
+    call!("a");
+    c.verify(Call::par([Call::any(["a", "b"]), Call::id("c")]));
+}
+
+#[test]
+fn optional_in_par() {
+    let mut c = CallRecorder::new_local();
+    call!("c");
+    call!("a");
+    call!("b");
+    c.verify(Call::par([
+        Call::seq([Call::optional("a"), Call::id("b")]),
+        Call::id("c"),
+    ]));
+}
+
+#[test]
+fn verify_prefix() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    call!("3");
+    c.verify_prefix(["1", "2"]);
+    call!("4");
+    c.verify(["3", "4"]);
+}
+
+#[test]
+fn verify_prefix_global() {
+    let mut c = CallRecorder::new();
+    call!("1");
+    spawn(|| call!("2")).join().unwrap();
+    c.verify_prefix("1");
+    c.verify_prefix("2");
+    c.verify(());
+}
+
+#[test]
+fn verify_prefix_all() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    c.verify_prefix(["1", "2"]);
+    c.verify(());
+}
+
+#[should_panic]
+#[test]
+fn verify_prefix_fail_not_call() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    c.verify_prefix(["1", "2"]);
+}
+
+#[should_panic]
+#[test]
+fn verify_prefix_fail_mismatch() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("3");
+    c.verify_prefix(["1", "2"]);
+}
+
+#[test]
+fn verify_not_called() {
+    let mut c = CallRecorder::new_local();
+    call!("db.begin");
+    call!("db.commit");
+    c.verify_not_called("db.rollback");
+    c.verify_not_called(["db.commit", "db.begin"]);
+    c.verify(["db.begin", "db.commit"]);
+}
+
+#[test]
+fn verify_not_called_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("db.begin");
+    call!("db.rollback");
+    let e = catch_unwind(AssertUnwindSafe(|| c.verify_not_called("db.rollback"))).unwrap_err();
+    let msg = e.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("unexpected call"), "{msg}");
+    assert!(msg.contains("actual : db.rollback"), "{msg}");
+    assert!(msg.contains("expect : not(db.rollback)"), "{msg}");
+}
+
+#[should_panic]
+#[test]
+fn verify_not_called_fail_seq() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    call!("3");
+    c.verify_not_called(["2", "3"]);
+}
+
+#[test]
+fn not() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    c.verify([Call::not("2"), Call::not("1")]);
+}
+
+#[should_panic]
+#[test]
+fn not_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    c.verify(Call::not("1"));
+}
+
+#[test]
+fn count() {
+    let mut c = CallRecorder::new_local();
+    assert!(c.is_empty());
+    call!("poll");
+    call!("poll");
+    call!("done");
+    assert!(!c.is_empty());
+    assert_eq!(c.count("poll"), 2);
+    assert_eq!(c.count("done"), 1);
+    assert_eq!(c.count("none"), 0);
+    assert_eq!(c.count_matching(Call::any(["poll", "done"])), 3);
+    assert_eq!(c.count_matching(["poll", "poll"]), 1);
+    assert_eq!(c.count_matching(Call::empty()), 0);
+    c.verify(["poll", "poll", "done"]);
+    assert!(c.is_empty());
+}
+
+#[test]
+fn snapshot() {
+    let mut c = CallRecorder::new();
+    call!("1");
+    let line = line!() - 1;
+    let thread_id = spawn(|| {
+        call!("2");
+        current().id()
+    })
+    .join()
+    .unwrap();
+
+    let s = c.snapshot();
+    assert_eq!(s.len(), 2);
+    assert_eq!(s[0].id(), "1");
+    assert_eq!(s[0].file(), file!());
+    assert_eq!(s[0].line(), line);
+    assert_eq!(s[0].thread_id(), current().id());
+    assert_eq!(s[1].id(), "2");
+    assert_eq!(s[1].thread_id(), thread_id);
+
+    c.verify(["1", "2"]);
+}
+
+#[test]
+fn take() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    let records = c.take();
+    assert!(c.is_empty());
+    assert_eq!(records.len(), 2);
+    let ids: Vec<_> = records.iter().map(|r| r.id().to_string()).collect();
+    assert_eq!(ids, ["1", "2"]);
+    let ids: Vec<_> = records.into_iter().map(|r| r.id().to_string()).collect();
+    assert_eq!(ids, ["1", "2"]);
+}
+
+#[test]
+fn fields() {
+    let mut c = CallRecorder::new_local();
+    let n = 1024;
+    call!("write"; fd = 3, len = n);
+    call!("write-{}", 2; name = "a", flag = true,);
+    call!("write"; fd = 3);
+    let s = c.snapshot();
+    assert_eq!(s[0].field("fd"), Some("3"));
+    assert_eq!(s[1].field("name"), Some("\"a\""));
+    assert_eq!(
+        s[1].fields().collect::<Vec<_>>(),
+        [("name", "\"a\""), ("flag", "true")]
+    );
+    c.verify([
+        Call::id("write").with("len", 1024),
+        Call::glob("write-*").with("name", "a"),
+        Call::id("write"),
+    ]);
+}
+
+#[test]
+fn fields_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("write"; fd = 4);
+    let e = c
+        .check(Call::id("write").with("fd", 3).with("len", 1))
+        .unwrap_err();
+    assert_eq!(e.expect(), ["write { fd = 3, len = 1 }"]);
+    assert_eq!(
+        e.field_diffs(),
+        ["fd : actual 4, expect 3", "len : actual (none), expect 1"]
+    );
+}
+
+#[test]
+fn call_result() {
+    fn fetch(s: &str) -> Result<u32, String> {
+        call_result!("fetch", {
+            call!("parse");
+            s.parse().map_err(|_| format!("invalid : {s}"))
+        })
+    }
+    let mut c = CallRecorder::new_local();
+    assert_eq!(fetch("1"), Ok(1));
+    assert!(fetch("a").is_err());
+
+    let s = c.snapshot();
+    assert_eq!(s[0].outcome(), Some(Ok("1")));
+    assert_eq!(s[1].outcome(), None);
+    assert_eq!(s[2].outcome(), Some(Err(r#""invalid : a""#)));
+    c.verify([
+        Call::id("fetch").returns_ok(),
+        Call::id("parse"),
+        Call::id("fetch").returns_err(),
+        Call::id("parse"),
+    ]);
+}
+
+#[should_panic]
+#[test]
+fn returns_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("fetch");
+    c.verify(Call::id("fetch").returns_ok());
+}
+
+#[test]
+fn call_span() {
+    fn a() {
+        let _s = call_span!("a-{}", 1);
+        call!("1");
+        b();
+    }
+    fn b() {
+        let _s = call_span!("b");
+        call!("2");
+    }
+    let mut c = CallRecorder::new_local();
+    a();
+    let s = c.snapshot();
+    assert_eq!(s[0].kind(), RecordKind::Enter);
+    assert_eq!(s[0].depth(), 0);
+    assert_eq!(s[1].kind(), RecordKind::Call);
+    assert_eq!(s[1].depth(), 1);
+    assert_eq!(s[5].kind(), RecordKind::Exit);
+    assert_eq!(c.count("a-1"), 0);
+    c.verify(Call::within("a-1", [Call::id("1"), Call::within("b", "2")]));
+}
+
+#[test]
+fn call_span_unwind() {
+    let mut c = CallRecorder::new_local();
+    let _ = catch_unwind(|| {
+        let _s = call_span!("a");
+        call!("1");
+        panic!("error");
+    });
+    c.verify(Call::within("a", "1"));
+}
+
+#[should_panic]
+#[test]
+fn call_span_fail_outside() {
+    let mut c = CallRecorder::new_local();
+    {
+        let _s = call_span!("a");
+    }
+    call!("1");
+    c.verify(Call::within("a", "1"));
+}
+
+#[test]
+fn time() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    sleep(Duration::from_millis(20));
+    call!("2");
+    let s = c.snapshot();
+    assert_eq!(s[0].delay(), None);
+    assert!(s[1].delay().unwrap() >= Duration::from_millis(20));
+    assert!(s[1].time() - s[0].time() >= Duration::from_millis(20));
+    c.verify(["1", "2"]);
+}
+
+#[test]
+fn after_delay() {
+    let mut c = CallRecorder::new_local();
+    call!("try");
+    sleep(Duration::from_millis(20));
+    call!("retry");
+    c.verify([
+        Call::id("try"),
+        Call::id("retry").after_delay(Duration::from_millis(20)),
+    ]);
+}
+
+#[test]
+fn after_delay_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("try");
+    call!("retry");
+    let e = c
+        .check([
+            Call::id("try"),
+            Call::id("retry").after_delay(Duration::from_secs(10)),
+        ])
+        .unwrap_err();
+    assert_eq!(e.mismatch_index(), 1);
+    assert!(e.field_diffs()[0].starts_with("delay : actual "));
+    assert!(e.display(false, false).to_string().contains("retry (+"));
+}
+
+#[test]
+fn no_time_without_timing_condition() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    let e = c.check(["1", "3"]).unwrap_err();
+    assert!(!e.display(true, false).to_string().contains("(+"));
+}
+
+#[test]
+fn completes_within() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    call!("2");
+    c.verify(Call::seq(["1", "2"]).completes_within(Duration::from_secs(10)));
+}
+
+#[test]
+fn completes_within_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    sleep(Duration::from_millis(20));
+    call!("2");
+    let e = c
+        .check(Call::seq(["1", "2"]).completes_within(Duration::from_millis(10)))
+        .unwrap_err();
+    assert_eq!(e.mismatch_index(), 1);
+    assert!(e.field_diffs()[0].starts_with("elapsed : actual "));
+    assert!(e.display(false, false).to_string().contains("2 (+"));
+}
+
+#[test]
+fn manual_clock() {
+    let clock = ManualClock::new();
+    let mut c = CallRecorder::new_local().with_clock(clock.clone());
+    call!("1");
+    clock.advance(Duration::from_millis(100));
+    call!("2");
+    call!("3");
+    let s = c.snapshot();
+    assert_eq!(s[1].delay(), Some(Duration::from_millis(100)));
+    assert_eq!(s[2].delay(), Some(Duration::ZERO));
+    c.verify([
+        Call::id("1"),
+        Call::id("2").after_delay(Duration::from_millis(100)),
+        Call::id("3"),
+    ]);
+}
+
+#[test]
+fn manual_clock_completes_within_fail() {
+    let clock = ManualClock::new();
+    let mut c = CallRecorder::new_local().with_clock(clock.clone());
+    call!("1");
+    clock.advance(Duration::from_millis(51));
+    call!("2");
+    let e = c
+        .check(Call::seq(["1", "2"]).completes_within(Duration::from_millis(50)))
+        .unwrap_err();
+    assert_eq!(e.field_diffs(), ["elapsed : actual 51ms, expect <= 50ms"]);
+}
+
+#[test]
+fn on_thread() {
+    let mut c = CallRecorder::new();
+    let (a, b) = scope(|s| {
+        let a = s.spawn(|| {
+            call!("1");
+            call!("2");
+        });
+        let a_id = a.thread().id();
+        a.join().unwrap();
+        let b = s.spawn(|| {
+            call!("1");
+            call!("2");
+        });
+        (a_id, b.thread().id())
+    });
+    c.verify(Call::par([
+        Call::on_thread(a, ["1", "2"]),
+        Call::on_thread(b, ["1", "2"]),
+    ]));
+}
+
+#[test]
+fn on_thread_fail() {
+    let mut c = CallRecorder::new();
+    call!("1");
+    let other = spawn(|| {}).thread().id();
+    let e = c.check(Call::on_thread(other, "1")).unwrap_err();
+    assert_eq!(
+        e.field_diffs(),
+        [format!(
+            "thread : actual {}, expect {other:?}",
+            current().name().unwrap()
+        )]
+    );
+}
+
+#[test]
+fn verify_per_thread() {
+    let mut c = CallRecorder::new();
+    call!("main");
+    let (a, b) = scope(|s| {
+        let a = s.spawn(|| {
+            for i in 0..10 {
+                call!("a{i}");
+            }
+        });
+        let b = s.spawn(|| {
+            for i in 0..10 {
+                call!("b{i}");
+            }
+        });
+        (a.thread().id(), b.thread().id())
+    });
+    let a_ids: Vec<_> = (0..10).map(|i| format!("a{i}")).collect();
+    let b_ids: Vec<_> = (0..10).map(|i| format!("b{i}")).collect();
+    c.verify_per_thread([
+        (current().id(), Call::id("main")),
+        (a, Call::seq(a_ids)),
+        (b, Call::seq(b_ids)),
+    ]);
+}
+
+#[should_panic]
+#[test]
+fn verify_per_thread_unlisted() {
+    let mut c = CallRecorder::new();
+    call!("main");
+    scope(|s| {
+        s.spawn(|| call!("1"));
+    });
+    c.verify_per_thread([(current().id(), "main")]);
+}
+
+#[should_panic]
+#[test]
+fn verify_per_thread_missing() {
+    let mut c = CallRecorder::new();
+    call!("main");
+    let other = spawn(|| {}).thread().id();
+    c.verify_per_thread([(current().id(), "main"), (other, "1")]);
+}
+
+#[test]
+fn thread_name() {
+    let mut c = CallRecorder::new();
+    call!("main");
+    Builder::new()
+        .name("worker".into())
+        .spawn(|| call!("1"))
+        .unwrap()
+        .join()
+        .unwrap();
+    let s = c.snapshot();
+    assert_eq!(s[0].thread_name(), current().name());
+    assert_eq!(s[1].thread_name(), Some("worker"));
+    c.verify_per_thread([
+        (ThreadTag::from(current().id()), "main"),
+        ("worker".into(), "1"),
+    ]);
+}
+
+#[should_panic(expected = "thread name `worker` matches 2 threads")]
+#[test]
+fn verify_per_thread_ambiguous_name() {
+    let mut c = CallRecorder::new();
+    for _ in 0..2 {
+        Builder::new()
+            .name("worker".into())
+            .spawn(|| call!("1"))
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+    c.verify_per_thread([("worker", ["1", "1"])]);
+}
+
+#[test]
+fn thread_name_display() {
+    let mut c = CallRecorder::new();
+    call!("main");
+    Builder::new()
+        .name("worker".into())
+        .spawn(|| call!("1"))
+        .unwrap()
+        .join()
+        .unwrap();
+    let e = c.check(["main", "2"]).unwrap_err();
+    let s = e.display(true, false).to_string();
+    assert!(s.contains("1 [worker]"), "{s}");
+    assert!(s.contains("thread : thread_name_display"), "{s}");
+}
+
+#[test]
+fn on_thread_name() {
+    let mut c = CallRecorder::new();
+    scope(|s| {
+        Builder::new()
+            .name("worker".into())
+            .spawn_scoped(s, || call!("1"))
+            .unwrap();
+    });
+    c.verify(Call::on_thread("worker", "1"));
+}
+
+#[test]
+fn on_thread_name_fail() {
+    let mut c = CallRecorder::new();
+    scope(|s| {
+        Builder::new()
+            .name("worker-1".into())
+            .spawn_scoped(s, || call!("1"))
+            .unwrap();
+    });
+    let e = c.check(Call::on_thread("worker-2", "1")).unwrap_err();
+    assert_eq!(
+        e.field_diffs(),
+        ["thread : actual worker-1, expect worker-2"]
+    );
+}
+
+#[test]
+fn verify_eventually() {
+    let mut c = CallRecorder::new();
+    let h = spawn(|| {
+        sleep(Duration::from_millis(20));
+        call!("1");
+        sleep(Duration::from_millis(20));
+        call!("2");
+    });
+    c.verify_eventually(["1", "2"], Duration::from_secs(10));
+    h.join().unwrap();
+}
+
+#[test]
+fn verify_eventually_mismatch() {
+    let mut c = CallRecorder::new();
+    let h = spawn(|| {
+        call!("1");
+        call!("3");
+    });
+    let start = std::time::Instant::now();
+    let r = catch_unwind(AssertUnwindSafe(|| {
+        c.verify_eventually(["1", "2"], Duration::from_secs(10))
+    }));
+    assert!(r.is_err());
+    assert!(start.elapsed() < Duration::from_secs(10));
+    h.join().unwrap();
+}
+
+#[test]
+fn verify_eventually_timeout() {
+    let mut c = CallRecorder::new();
+    call!("1");
+    let e = catch_unwind(AssertUnwindSafe(|| {
+        c.verify_eventually(["1", "2"], Duration::from_millis(20))
+    }))
+    .unwrap_err();
+    let msg = e.downcast_ref::<String>().unwrap();
+    assert!(msg.contains("waited : "), "{msg}");
+}
+
+#[test]
+fn verify_eventually_local() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    c.verify_eventually("1", Duration::from_secs(10));
+}
+
+#[test]
+fn verify_eventually_max_timeout() {
+    let mut c = CallRecorder::new();
+    let h = spawn(|| {
+        sleep(Duration::from_millis(20));
+        call!("1");
+    });
+    c.verify_eventually("1", Duration::MAX);
+    h.join().unwrap();
+}
+
+#[test]
+fn wait_for() {
+    let mut c = CallRecorder::new();
+    let h = spawn(|| {
+        call!("1");
+        sleep(Duration::from_millis(20));
+        call!("2");
+        call!("3");
+    });
+    assert_eq!(c.wait_for(["2", "3"], Duration::from_secs(10)).unwrap(), 2);
+    h.join().unwrap();
+    c.verify(["1", "2", "3"]);
+}
+
+#[test]
+fn wait_for_timeout() {
+    let mut c = CallRecorder::new();
+    call!("1");
+    let e = c.wait_for("2", Duration::from_millis(20)).unwrap_err();
+    assert_eq!(e.expect(), "2");
+    assert_eq!(e.timeout(), Duration::from_millis(20));
+    c.verify("1");
+}
+
+#[test]
+fn wait_for_max_timeout() {
+    let mut c = CallRecorder::new();
+    let h = spawn(|| {
+        sleep(Duration::from_millis(20));
+        call!("1");
+    });
+    assert_eq!(c.wait_for("1", Duration::MAX).unwrap(), 0);
+    h.join().unwrap();
+    c.verify("1");
+}
+
+#[test]
+fn wait_for_local() {
+    let c = CallRecorder::new_local();
+    call!("1");
+    assert_eq!(c.wait_for("1", Duration::from_secs(10)).unwrap(), 0);
+    assert!(c.wait_for("2", Duration::from_secs(10)).is_err());
+}
+
+#[test]
+fn expect_on_drop() {
+    let _c = CallRecorder::new_local().expect_on_drop(["1", "2"]);
+    call!("1");
+    call!("2");
+}
+
+#[should_panic]
+#[test]
+fn expect_on_drop_fail() {
+    let _c = CallRecorder::new_local().expect_on_drop(["1", "2"]);
+    call!("1");
+}
+
+#[test]
+fn deny_unverified() {
+    let mut c = CallRecorder::new_local().deny_unverified();
+    call!("1");
+    c.verify("1");
+}
+
+#[should_panic]
+#[test]
+fn deny_unverified_fail() {
+    let _c = CallRecorder::new_local().deny_unverified();
+    call!("1");
+}
+
+#[should_panic(expected = "error")]
+#[test]
+fn deny_unverified_panicking() {
+    let _c = CallRecorder::new_local().deny_unverified();
+    call!("1");
+    panic!("error");
+}
+
+#[test]
+fn call_if_recording() {
+    let mut c = CallRecorder::new_local();
+    call_if_recording!("1");
+    call_if_recording!("{}-{}", 2, 3);
+    call_if_recording!("write"; fd = 3);
+    c.verify([
+        Call::id("1"),
+        Call::id("2-3"),
+        Call::id("write").with("fd", 3),
+    ]);
+}
+
+#[test]
+fn verify_eventually_local_not_waited() {
+    let mut c = CallRecorder::new_local();
+    call!("1");
+    let e = catch_unwind(AssertUnwindSafe(|| {
+        c.verify_eventually(["1", "2"], Duration::from_secs(10))
+    }))
+    .unwrap_err();
+    let msg = e.downcast_ref::<String>().unwrap();
+    assert!(!msg.contains("waited : "), "{msg}");
+}
+
+#[test]
+fn call_span_other_thread_fail() {
+    let mut c = CallRecorder::new();
+    {
+        let _s = call_span!("a");
+        spawn(|| call!("bg")).join().unwrap();
+    }
+    let e = c.check(Call::within("a", "bg")).unwrap_err();
+    assert_eq!(e.mismatch_index(), 1);
+    assert_eq!(e.field_diffs(), ["span : actual (outside), expect a"]);
+}
+
+#[test]
+fn call_span_other_thread() {
+    let mut c = CallRecorder::new();
+    {
+        let _s = call_span!("a");
+        call!("1");
+        spawn(|| call!("bg")).join().unwrap();
+        call!("2");
+    }
+    c.verify(Call::par([Call::within("a", ["1", "2"]), Call::id("bg")]));
+}
+
+#[should_panic]
+#[test]
+fn call_span_extra_inside() {
+    let mut c = CallRecorder::new_local();
+    {
+        let _s = call_span!("a");
+        call!("1");
+        call!("2");
+    }
+    c.verify(Call::within("a", "1"));
+}
+
+#[test]
+fn ignore_others_then_sibling() {
+    let mut c = CallRecorder::new_local();
+    call!("log");
+    call!("a");
+    call!("b");
+    c.verify([Call::ignore_others("a"), Call::id("b")]);
+}
+
+#[should_panic]
+#[test]
+fn ignore_others_then_sibling_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    call!("log");
+    call!("b");
+    c.verify([Call::ignore_others("a"), Call::id("b")]);
+}
+
+#[test]
+fn verify_prefix_ignore_others() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    call!("b");
+    call!("c");
+    c.verify_prefix(Call::ignore_others("b"));
+    c.verify("c");
+}
+
+#[test]
+fn repeat_then_same() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    call!("a");
+    c.verify([Call::repeat("a"), Call::id("a")]);
+}
+
+#[test]
+fn repeat_then_same_empty() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    c.verify([Call::repeat("a"), Call::id("a")]);
+}
+
+#[test]
+fn at_least_then_same() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    call!("a");
+    call!("a");
+    c.verify([Call::at_least("a", 2), Call::id("a"), Call::repeat("b")]);
+}
+
+#[should_panic]
+#[test]
+fn at_least_then_same_fail() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    call!("a");
+    c.verify([Call::at_least("a", 2), Call::id("a")]);
+}
+
+#[test]
+fn optional_then_same() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    c.verify([Call::optional("a"), Call::id("a")]);
+    call!("a");
+    call!("a");
+    c.verify([Call::optional("a"), Call::id("a")]);
+}
+
+#[test]
+fn optional_seq_then_same() {
+    let mut c = CallRecorder::new_local();
+    call!("a");
+    call!("c");
+    c.verify([Call::optional(["a", "b"]), Call::id("a"), Call::id("c")]);
+}